@@ -0,0 +1,182 @@
+//! Output backends, which determine how index markup and the index page are emitted for a particular renderer.
+
+use crate::{Index, Location};
+use std::path::Path;
+
+mod asciidoc;
+mod docbook;
+mod epub;
+mod html;
+mod latex;
+mod pandoc;
+mod skip;
+mod typst;
+
+pub use asciidoc::{asciidoc_protect, text_to_asciidoc};
+
+/// Name of the backend used for renderers that have no other mapping, and so aren't known to handle HTML.
+pub(crate) const FALLBACK_BACKEND: &str = "skip";
+
+/// Known renderers, and the backend that suits the output each of them produces.
+const RENDERER_BACKENDS: &[(&str, &str)] = &[
+    ("html", "html"),
+    ("asciidoc", "asciidoc"),
+    ("docbook", "docbook"),
+    ("epub", "epub"),
+    ("latex", "latex"),
+    ("pandoc", "pandoc"),
+    ("typst", "typst"),
+    // Renderers that emit (or check) Markdown text, which has no use for index anchors.
+    ("markdown", "skip"),
+    ("linkcheck", "skip"),
+    ("linkcheck2", "skip"),
+];
+
+/// An occurrence of index markup in a chapter.
+#[derive(Clone, Debug)]
+pub struct Marker<'a> {
+    /// File of the chapter containing the markup, if any.
+    pub path: Option<&'a Path>,
+    /// Text of the markup, as it appears in the source.
+    pub content: &'a str,
+    /// Canonicalized index entry for the markup.
+    pub entry: &'a str,
+    /// Index entry that the location is accumulated against, which differs from `entry` for see-instead entries.
+    pub target: &'a str,
+    /// Whether the text of the markup is included in the output.
+    pub visible: bool,
+    /// Whether the text of the markup is italicized in the output.
+    pub italic: bool,
+    /// Anchor identifier for the location, if the backend collects locations.
+    pub anchor: Option<String>,
+}
+
+impl Marker<'_> {
+    /// Return the text that the markup contributes to the output.
+    pub fn text(&self) -> String {
+        if !self.visible {
+            "".to_string()
+        } else if self.italic {
+            format!("*{}*", self.content)
+        } else {
+            self.content.to_string()
+        }
+    }
+}
+
+/// Output format for index markup and for the index page.
+pub trait IndexBackend {
+    /// Indicate whether index locations should be collected (with an anchor for each), for use when generating
+    /// the index page.
+    fn collects_locations(&self) -> bool {
+        false
+    }
+
+    /// Escape index text so that it is suitable for inclusion in the output.
+    fn escape(&self, text: &str) -> String;
+
+    /// Emit the output for an occurrence of index markup.
+    fn marker(&self, index: &Index, marker: &Marker) -> String;
+
+    /// Generate the contents of the index page.
+    fn index_page(&self, index: &Index) -> String;
+
+    /// Generate the contents of the glossary page.
+    ///
+    /// By default this is plain Markdown, with no links to the index.
+    fn glossary_page(&self, index: &Index) -> String {
+        let mut result = format!("# {}\n\n", index.strings.glossary);
+        for (term, definition) in index.glossary_entries() {
+            result += &format!("**{term}**: {}\n\n", definition.unwrap_or_default());
+        }
+        result
+    }
+
+    /// Generate a compact index of the given entries and their locations in part of the book, for inclusion in the
+    /// chapter at `from`.
+    ///
+    /// Backends that don't collect locations have no entries to list, so emit nothing by default.
+    fn scoped_index(
+        &self,
+        _index: &Index,
+        _entries: &[(String, Vec<Location>)],
+        _from: Option<&Path>,
+    ) -> String {
+        String::new()
+    }
+}
+
+/// Return the backend with the given name, if there is one.
+pub fn by_name(name: &str) -> Option<Box<dyn IndexBackend>> {
+    match name {
+        "html" => Some(Box::new(html::Html)),
+        "asciidoc" => Some(Box::new(asciidoc::AsciiDoc)),
+        "docbook" => Some(Box::new(docbook::DocBook)),
+        "epub" => Some(Box::new(epub::Epub)),
+        "latex" => Some(Box::new(latex::Latex)),
+        "pandoc" => Some(Box::new(pandoc::Pandoc)),
+        "typst" => Some(Box::new(typst::Typst)),
+        "skip" => Some(Box::new(skip::Skip)),
+        _ => None,
+    }
+}
+
+/// Return the name of the backend to use for a renderer that has no configured mapping.
+pub fn default_for(renderer: &str) -> &'static str {
+    RENDERER_BACKENDS
+        .iter()
+        .find(|(name, _)| *name == renderer)
+        .map(|(_, backend)| *backend)
+        .unwrap_or_else(|| {
+            log::info!(
+                "Renderer '{renderer}' not known to handle HTML, using '{FALLBACK_BACKEND}' backend"
+            );
+            FALLBACK_BACKEND
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_backends() {
+        let cases = [
+            ("html", "html"),
+            ("asciidoc", "asciidoc"),
+            ("latex", "latex"),
+            ("typst", "typst"),
+            ("epub", "epub"),
+            ("pandoc", "pandoc"),
+            ("markdown", "skip"),
+            ("linkcheck", "skip"),
+            ("custom", "skip"),
+        ];
+        for (renderer, want) in cases {
+            let got = default_for(renderer);
+            assert_eq!(got, want, "Mismatch for renderer: {}", renderer);
+        }
+        for (_, backend) in RENDERER_BACKENDS {
+            assert!(by_name(backend).is_some(), "Unknown backend: {}", backend);
+        }
+    }
+
+    #[test]
+    fn test_marker_text() {
+        let mut marker = Marker {
+            path: None,
+            content: "some text",
+            entry: "some text",
+            target: "some text",
+            visible: true,
+            italic: false,
+            anchor: None,
+        };
+        assert_eq!(marker.text(), "some text");
+        marker.italic = true;
+        assert_eq!(marker.text(), "*some text*");
+        marker.visible = false;
+        marker.italic = false;
+        assert_eq!(marker.text(), "");
+    }
+}
@@ -0,0 +1,150 @@
+//! Backend for AsciiDoc output, for use with [`mdbook-asciidoc`](https://github.com/daviddrysdale/mdbook-asciidoc).
+
+use super::{IndexBackend, Marker};
+use crate::Index;
+
+/// Backend that emits AsciiDoc concealed index terms.
+pub(crate) struct AsciiDoc;
+
+impl AsciiDoc {
+    /// Generate the comma-separated terms for an entry, with one term per level of nesting.
+    fn terms(&self, index: &Index, entry: &str) -> String {
+        index
+            .level_displays(entry)
+            .into_iter()
+            .map(|level| {
+                let mut term = self.escape(level);
+                asciidoc_protect(&mut term);
+                term
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Generate a quoted attribute value referring to other entries.
+    fn reference(&self, entries: &[String]) -> String {
+        let entries = entries
+            .iter()
+            .map(|entry| self.escape(entry))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("\"{}\"", entries.replace('"', "\\\""))
+    }
+}
+
+impl IndexBackend for AsciiDoc {
+    fn escape(&self, text: &str) -> String {
+        text_to_asciidoc(text)
+    }
+
+    fn marker(&self, index: &Index, marker: &Marker) -> String {
+        // Use concealed index terms, which (unlike the `indexterm:[]` macro) need no
+        // space after them.
+        let mut terms = self.terms(index, marker.target);
+        if let Some(see_also) = index.see_also.get(marker.target) {
+            terms += &format!(",see-also={}", self.reference(see_also));
+        }
+        let mut result = format!("((({terms})))");
+        if marker.target != marker.entry {
+            // Emit a see-reference from the original entry to where its locations are collected.
+            result += &format!(
+                "((({},see={})))",
+                self.terms(index, marker.entry),
+                self.reference(&[marker.target.to_string()])
+            );
+        }
+        log::debug!("asciidoc entry '{result}'");
+        result + &marker.text()
+    }
+
+    fn index_page(&self, index: &Index) -> String {
+        // AsciiDoc takes care of generating the index catalog.
+        format!("[index]\n== {}\n", index.strings.title)
+    }
+}
+
+/// Convert index text into a form suitable for AsciiDoc.
+pub fn text_to_asciidoc(text: &str) -> String {
+    // Remove surrounding MarkDown formatting characters and substitute for special characters.
+    text.replace('`', "")
+        .trim_matches('*')
+        .trim_matches('_')
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Protect a string from AsciiDoc intepretation
+/// - Add quotes round a string if it contains commas.
+/// - Use a passthrough macro if it contains character replacement substitutions.
+pub fn asciidoc_protect(text: &mut String) {
+    if text.contains(',') {
+        // An index entry with a comma needs double quotes around it so
+        // the comma doesn't induce a nested entry.
+        let quoted_text = format!("\"{text}\"");
+        *text = quoted_text;
+    }
+    if text.contains("(C)") {
+        // Avoid (C) being interpreted as a copyright sign. (Source can always use &#169; to get one anyway.)
+        let pass_text = format!("pass:[{text}]");
+        *text = pass_text;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_marker() {
+        let index = Index {
+            see_instead: HashMap::from([("unit test".to_string(), "test, unit".to_string())]),
+            see_also: HashMap::from([(
+                "test".to_string(),
+                vec!["coverage".to_string(), "`assert!`".to_string()],
+            )]),
+            nest_under: HashMap::from([
+                ("test, unit".to_string(), "test".to_string()),
+                ("test, unit, doc".to_string(), "test, unit".to_string()),
+            ]),
+            suppress_head: true,
+            ..Default::default()
+        };
+        let marker = |content, entry, target, visible| Marker {
+            path: None,
+            content,
+            entry,
+            target,
+            visible,
+            italic: false,
+            anchor: None,
+        };
+        let cases = [
+            (marker("text", "text", "text", true), "(((text)))text"),
+            (marker("a, b", "a, b", "a, b", false), "(((\"a, b\")))"),
+            (marker("<&>", "<&>", "<&>", false), "(((&lt;&amp;&gt;)))"),
+            (
+                marker("test", "test", "test", true),
+                "(((test,see-also=\"coverage, assert!\")))test",
+            ),
+            (
+                marker(
+                    "test, unit, doc",
+                    "test, unit, doc",
+                    "test, unit, doc",
+                    false,
+                ),
+                "(((test,unit,doc)))",
+            ),
+            (
+                marker("unit test", "unit test", "test, unit", true),
+                "(((test,unit)))(((unit test,see=\"test, unit\")))unit test",
+            ),
+        ];
+        for (marker, want) in cases {
+            let got = AsciiDoc.marker(&index, &marker);
+            assert_eq!(got, want, "Mismatch for marker: {:?}", marker);
+        }
+    }
+}
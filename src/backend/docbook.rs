@@ -0,0 +1,140 @@
+//! Backend for DocBook output, emitting raw `<indexterm>` elements.
+
+use super::{IndexBackend, Marker};
+use crate::Index;
+
+/// Element names for each level of nesting in an `<indexterm>`.
+const LEVEL_TAGS: [&str; crate::MAX_LEVELS] = ["primary", "secondary", "tertiary"];
+
+/// Backend that emits DocBook `<indexterm>` elements.
+pub(crate) struct DocBook;
+
+impl DocBook {
+    /// Generate an element holding index text, with a `sortas` attribute when the display form includes formatting or
+    /// there is an explicit sort key.
+    fn element(&self, tag: &str, text: &str, sort: Option<&String>) -> String {
+        let (sort_key, display) = self.text_to_docbook(text);
+        let sort_key = sort.map_or(sort_key, |sort| self.escape(sort));
+        if sort_key == display {
+            format!("<{tag}>{display}</{tag}>")
+        } else {
+            format!("<{tag} sortas=\"{sort_key}\">{display}</{tag}>")
+        }
+    }
+
+    /// Generate an `<indexterm>` for an entry, with one element per level of nesting, followed by `extra` content.
+    fn indexterm(&self, index: &Index, entry: &str, extra: &str) -> String {
+        let levels = index
+            .levels(entry)
+            .into_iter()
+            .zip(index.level_displays(entry))
+            .zip(LEVEL_TAGS)
+            .map(|((level, display), tag)| self.element(tag, display, index.sort_as.get(level)))
+            .collect::<String>();
+        format!("<indexterm>{levels}{extra}</indexterm>")
+    }
+
+    /// Convert Markdown index text into DocBook, returning `(sort_key, display)`.
+    ///
+    /// Code spans are rendered with `<literal>`, and an entry surrounded by Markdown emphasis is rendered with
+    /// `<emphasis>`.
+    fn text_to_docbook(&self, text: &str) -> (String, String) {
+        let plain = text.trim_matches('*').trim_matches('_');
+        let mut sort_key = String::new();
+        let mut display = String::new();
+        for (idx, part) in plain.split('`').enumerate() {
+            let escaped = self.escape(part);
+            sort_key += &escaped;
+            if idx % 2 == 1 {
+                display += &format!("<literal>{escaped}</literal>");
+            } else {
+                display += &escaped;
+            }
+        }
+        if plain.len() != text.len() {
+            display = format!("<emphasis>{display}</emphasis>");
+        }
+        (sort_key, display)
+    }
+}
+
+impl IndexBackend for DocBook {
+    fn escape(&self, text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    fn marker(&self, index: &Index, marker: &Marker) -> String {
+        let mut result = self.indexterm(index, marker.target, "");
+        if marker.target != marker.entry {
+            // Also emit the see-reference from the original entry.
+            let see = self.text_to_docbook(marker.target).1;
+            result += &self.indexterm(index, marker.entry, &format!("<see>{see}</see>"));
+        }
+        if let Some(see_also) = index.see_also.get(marker.target) {
+            let see_also = see_also
+                .iter()
+                .map(|alt| format!("<seealso>{}</seealso>", self.text_to_docbook(alt).1))
+                .collect::<String>();
+            result += &self.indexterm(index, marker.target, &see_also);
+        }
+        log::debug!("docbook entry '{result}'");
+        result + &marker.text()
+    }
+
+    fn index_page(&self, _index: &Index) -> String {
+        // The DocBook toolchain takes care of generating the index catalog.
+        "<index/>\n".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_marker() {
+        let index = Index {
+            see_instead: HashMap::from([("unit test".to_string(), "test, unit".to_string())]),
+            nest_under: HashMap::from([("test, unit".to_string(), "test".to_string())]),
+            sort_as: HashMap::from([("Müller".to_string(), "Mueller".to_string())]),
+            suppress_head: true,
+            ..Default::default()
+        };
+        let marker = |content, entry, target| Marker {
+            path: None,
+            content,
+            entry,
+            target,
+            visible: true,
+            italic: false,
+            anchor: None,
+        };
+        let cases = [
+            (
+                marker("text", "text", "text"),
+                "<indexterm><primary>text</primary></indexterm>text",
+            ),
+            (
+                marker("`Vec<T>`", "`Vec<T>`", "`Vec<T>`"),
+                "<indexterm><primary sortas=\"Vec&lt;T&gt;\"><literal>Vec&lt;T&gt;</literal></primary></indexterm>`Vec<T>`",
+            ),
+            (
+                marker("unit test", "unit test", "test, unit"),
+                "<indexterm><primary>test</primary><secondary>unit</secondary></indexterm>\
+                 <indexterm><primary>unit test</primary><see>test, unit</see></indexterm>unit test",
+            ),
+            (
+                marker("Müller", "Müller", "Müller"),
+                "<indexterm><primary sortas=\"Mueller\">Müller</primary></indexterm>Müller",
+            ),
+        ];
+        for (marker, want) in cases {
+            let got = DocBook.marker(&index, &marker);
+            assert_eq!(got, want, "Mismatch for marker: {:?}", marker);
+        }
+    }
+}
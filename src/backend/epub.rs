@@ -0,0 +1,427 @@
+//! Backend for EPUB 3 output, using the vocabulary of the [EPUB Indexes](https://www.w3.org/TR/epub-indexes/)
+//! specification.
+
+use super::{IndexBackend, Marker};
+use crate::{
+    group_by_part, locator_runs, relative_link, ByPart, Index, Location, Section, SubEntries,
+};
+use std::{borrow::Borrow, path::Path};
+
+/// Backend that emits `index-term` spans, and generates an index page that uses the EPUB index vocabulary.
+pub(crate) struct Epub;
+
+impl IndexBackend for Epub {
+    fn collects_locations(&self) -> bool {
+        true
+    }
+
+    fn escape(&self, text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    fn marker(&self, _index: &Index, marker: &Marker) -> String {
+        let anchor = marker.anchor.as_deref().unwrap_or_default();
+        format!(
+            "<span epub:type=\"index-term\" id=\"{anchor}\">{}</span>",
+            marker.text()
+        )
+    }
+
+    fn index_page(&self, index: &Index) -> String {
+        // The index is emitted as a single HTML block, so there must be no blank lines in it.
+        let mut result = String::new();
+        result += "<section epub:type=\"index\">\n";
+        result += &format!("<h1>{}</h1>\n", self.escape(&index.strings.title));
+        if index.by_part == ByPart::Sections {
+            for part in index.indexed_parts() {
+                if let Some(part) = &part {
+                    result += "<section epub:type=\"index-group\">\n";
+                    result += &format!("<h2>{}</h2>\n", self.escape(part));
+                }
+                result = self.append_groups(index, result, Some(part.as_deref()));
+                if part.is_some() {
+                    result += "</section>\n";
+                }
+            }
+        } else {
+            result = self.append_groups(index, result, None);
+        }
+        result += "</section>\n";
+        result
+    }
+
+    fn scoped_index(
+        &self,
+        index: &Index,
+        entries: &[(String, Vec<Location>)],
+        from: Option<&Path>,
+    ) -> String {
+        // The EPUB index vocabulary is only for the index itself, so use a plain list.
+        let mut result = String::from("<ul>\n");
+        for (entry, locations) in entries {
+            result += &format!("<li>{}", self.text_to_html(index.display(entry)));
+            for (idx, loc) in locations.iter().enumerate() {
+                let text = self.locator_text(index, idx, loc);
+                match self.href(loc, from) {
+                    Some(href) => {
+                        result +=
+                            &format!(", <a href=\"{href}\"{}>{text}</a>", self.title(index, loc))
+                    }
+                    None => result += &format!(", {text}"),
+                }
+            }
+            result += "</li>\n";
+        }
+        result += "</ul>\n";
+        result
+    }
+}
+
+impl Epub {
+    /// Append the entries of the index that have locations in `section`, in a group for each group heading.
+    fn append_groups(&self, index: &Index, mut result: String, section: Section) -> String {
+        // Group headings go beneath the heading of the section, if there is one.
+        let heading = if section.is_some() { "h3" } else { "h2" };
+        let (groups, sub_entries) = index.grouped_entries(section);
+        for (label, keys) in groups {
+            if let Some(label) = &label {
+                result += "<section epub:type=\"index-group\">\n";
+                result += &format!("<{heading}>{}</{heading}>\n", self.escape(label));
+            }
+            result += "<ul epub:type=\"index-entry-list\">\n";
+            for entry in keys {
+                let display = index.display(&entry);
+                result = self.append_entries(
+                    index,
+                    result,
+                    &sub_entries,
+                    section,
+                    std::slice::from_ref(&entry),
+                    display,
+                );
+            }
+            result += "</ul>\n";
+            if label.is_some() {
+                result += "</section>\n";
+            }
+        }
+        result
+    }
+
+    /// Append an entry to the generated index, including a nested list of its sub-entries.
+    fn append_entries(
+        &self,
+        index: &Index,
+        mut result: String,
+        sub_entries: &SubEntries,
+        section: Section,
+        chain: &[String],
+        entry_display: &str,
+    ) -> String {
+        let entry = chain.last().expect("chain of entries is not empty");
+        result += "<li epub:type=\"index-entry\">";
+        let mut term = self.text_to_html(entry_display);
+        if let Some(link) = index.links.get(entry) {
+            term = format!("<a href=\"{}\">{term}</a>", self.escape(link));
+        }
+        result += &format!("<span epub:type=\"index-term\">{term}</span>");
+        if !index.is_placed(chain) {
+            // Only a heading for the sub-entries, which are placed under it.
+        } else if let Some(alt) = index.see_instead.get(entry) {
+            result += &format!(
+                ", <span epub:type=\"index-xref-preferred\">{}</span>",
+                index.strings.see(&self.text_to_html(alt))
+            );
+        } else if let Some(locations) = index.section_locations(entry, section) {
+            if index.by_part == ByPart::Locators {
+                for (num, (part, locations)) in group_by_part(&locations).into_iter().enumerate() {
+                    result += if num == 0 { ", " } else { "; " };
+                    if let Some(part) = part {
+                        result += &format!("{}: ", self.escape(part));
+                    }
+                    result += &self.locators(index, &locations);
+                }
+            } else if !locations.is_empty() {
+                result += ", ";
+                result += &self.locators(index, &locations);
+            }
+        }
+        if let Some(see_also) = index.see_also.get(entry).filter(|_| index.is_placed(chain)) {
+            let see_also = see_also
+                .iter()
+                .map(|alt| self.text_to_html(alt))
+                .collect::<Vec<_>>()
+                .join(", ");
+            result += &format!(
+                ", <span epub:type=\"index-xref-related\">{}</span>",
+                index.strings.see_also(&see_also)
+            );
+        }
+        if let Some(description) = index
+            .descriptions
+            .get(entry)
+            .filter(|_| index.is_placed(chain))
+        {
+            result += &format!(
+                " {}",
+                index.strings.description(&self.text_to_html(description))
+            );
+        }
+        if let Some(subs) = sub_entries.get(chain) {
+            result += "\n<ul epub:type=\"index-entry-list\">\n";
+            for sub in subs.iter() {
+                let mut sub_chain = chain.to_vec();
+                sub_chain.push(sub.clone());
+                result = self.append_entries(
+                    index,
+                    result,
+                    sub_entries,
+                    section,
+                    &sub_chain,
+                    index.subentry(entry, sub),
+                );
+            }
+            result += "</ul>\n";
+        }
+        result += "</li>\n";
+        result
+    }
+
+    /// Generate the locator for the `idx`-th location of an entry.
+    fn locator(&self, index: &Index, idx: usize, loc: &Location) -> String {
+        let text = self.locator_text(index, idx, loc);
+        match self.href(loc, None) {
+            Some(href) => format!(
+                "<a epub:type=\"index-locator\" href=\"{href}\"{}>{text}</a>",
+                self.title(index, loc)
+            ),
+            None => text,
+        }
+    }
+
+    /// Return the comma-separated locators for a list of locations, with runs of locations in the same chapter shown
+    /// as a range if enabled (and locators are not chapter names).
+    fn locators<L: Borrow<Location>>(&self, index: &Index, locations: &[L]) -> String {
+        let locator = |idx: usize| self.locator(index, idx, locations[idx].borrow());
+        if !index.locator_ranges || index.use_chapter_names {
+            return (0..locations.len())
+                .map(locator)
+                .collect::<Vec<_>>()
+                .join(", ");
+        }
+        locator_runs(locations)
+            .into_iter()
+            .map(|(first, last)| {
+                if first == last {
+                    locator(first)
+                } else {
+                    let range = self.escape(&index.strings.range);
+                    format!("{}{range}{}", locator(first), locator(last))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Return the `title` attribute for a link to a location, holding a preview of the location if enabled.
+    fn title(&self, index: &Index, loc: &Location) -> String {
+        if index.previews && !loc.snippet.is_empty() {
+            format!(" title=\"{}\"", self.escape(&loc.snippet))
+        } else {
+            String::new()
+        }
+    }
+
+    /// Return the text of the locator for the `idx`-th location of an entry.
+    fn locator_text(&self, index: &Index, idx: usize, loc: &Location) -> String {
+        if index.use_chapter_names {
+            self.escape(&loc.name)
+        } else {
+            format!("{}", idx + 1)
+        }
+    }
+
+    /// Return the link to a location from the chapter at `from`, if the location is in a file.
+    fn href(&self, loc: &Location, from: Option<&Path>) -> Option<String> {
+        // Links in raw HTML don't get rewritten by the renderer, so refer to the output file directly.
+        let path = loc.path.as_ref()?.with_extension("html");
+        let from = from.map(|from| from.with_extension("html"));
+        Some(format!(
+            "{}#{}",
+            self.escape(&relative_link(from.as_deref(), &path)),
+            loc.anchor
+        ))
+    }
+
+    /// Convert Markdown index text into HTML, rendering code spans and surrounding emphasis.
+    fn text_to_html(&self, text: &str) -> String {
+        let plain = text.trim_matches('*').trim_matches('_');
+        let mut result = String::new();
+        for (idx, part) in plain.split('`').enumerate() {
+            if idx % 2 == 1 {
+                result += &format!("<code>{}</code>", self.escape(part));
+            } else {
+                result += &self.escape(part);
+            }
+        }
+        if plain.len() != text.len() {
+            result = format!("<em>{result}</em>");
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_deep_nesting() {
+        let index = Index {
+            nest_under: HashMap::from([
+                ("b".to_string(), "a".to_string()),
+                ("c".to_string(), "b".to_string()),
+                ("d".to_string(), "c".to_string()),
+                ("e".to_string(), "d".to_string()),
+            ]),
+            ..Default::default()
+        };
+        index.process_chapter(
+            &Epub,
+            &Some(PathBuf::from("ch1.md")),
+            None,
+            "Chapter",
+            "{{i:a}} {{i:b}} {{i:c}} {{i:d}} {{i:e}}",
+        );
+        let got = Epub.index_page(&index);
+        // Every entry appears once with its locator, including those that are too deep.
+        for (entry, anchor) in [
+            ("a", "a001"),
+            ("b", "a002"),
+            ("c", "a003"),
+            ("d", "a004"),
+            ("e", "a005"),
+        ] {
+            let want = format!(
+                "<span epub:type=\"index-term\">{entry}</span>, \
+                 <a epub:type=\"index-locator\" href=\"ch1.html#{anchor}\">1</a>"
+            );
+            assert_eq!(
+                got.matches(&want).count(),
+                1,
+                "Mismatch for entry {} in: {}",
+                entry,
+                got
+            );
+            assert_eq!(
+                got.matches(&format!(">{entry}<")).count(),
+                1,
+                "Duplicate {} in: {}",
+                entry,
+                got
+            );
+        }
+        // The lifted entries are at the deepest level, under "b".
+        assert!(got.contains("ch1.html#a004\">1</a></li>\n<li epub:type=\"index-entry\"><span epub:type=\"index-term\">e</span>"));
+    }
+
+    #[test]
+    fn test_text_to_html() {
+        let cases = [
+            ("abc", "abc"),
+            ("`code`", "<code>code</code>"),
+            ("`Vec<T>`", "<code>Vec&lt;T&gt;</code>"),
+            ("*italics*", "<em>italics</em>"),
+            ("R&D", "R&amp;D"),
+        ];
+        for (input, want) in cases {
+            let got = Epub.text_to_html(input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_index_page() {
+        let index = Index {
+            see_instead: HashMap::from([("unit test".to_string(), "test, unit".to_string())]),
+            nest_under: HashMap::from([("test, unit".to_string(), "test".to_string())]),
+            suppress_head: true,
+            ..Default::default()
+        };
+        let got = index.process_chapter(
+            &Epub,
+            &Some(PathBuf::from("ch1.md")),
+            None,
+            "Chapter",
+            "{{i:test}} {{hi:unit test}}",
+        );
+        assert_eq!(
+            got,
+            "<span epub:type=\"index-term\" id=\"a001\">test</span> \
+             <span epub:type=\"index-term\" id=\"a002\"></span>"
+        );
+        let want = r#"<section epub:type="index">
+<h1>Index</h1>
+<ul epub:type="index-entry-list">
+<li epub:type="index-entry"><span epub:type="index-term">test</span>, <a epub:type="index-locator" href="ch1.html#a001">1</a>
+<ul epub:type="index-entry-list">
+<li epub:type="index-entry"><span epub:type="index-term">unit</span>, <a epub:type="index-locator" href="ch1.html#a002">1</a></li>
+</ul>
+</li>
+<li epub:type="index-entry"><span epub:type="index-term">unit test</span>, <span epub:type="index-xref-preferred">see test, unit</span></li>
+</ul>
+</section>
+"#;
+        assert_eq!(Epub.index_page(&index), want);
+    }
+
+    #[test]
+    fn test_index_page_sections() {
+        let index = Index {
+            by_part: ByPart::Sections,
+            see_instead: HashMap::from([("unit test".to_string(), "test, unit".to_string())]),
+            nest_under: HashMap::from([("test, unit".to_string(), "test".to_string())]),
+            suppress_head: true,
+            parts: std::cell::RefCell::new(vec!["Part I".to_string()]),
+            ..Default::default()
+        };
+        index.process_chapter(
+            &Epub,
+            &Some(PathBuf::from("intro.md")),
+            None,
+            "Intro",
+            "{{i:test}}",
+        );
+        index.process_chapter(
+            &Epub,
+            &Some(PathBuf::from("ch1.md")),
+            Some("Part I"),
+            "Chapter",
+            "{{hi:unit test}}",
+        );
+        let want = r#"<section epub:type="index">
+<h1>Index</h1>
+<ul epub:type="index-entry-list">
+<li epub:type="index-entry"><span epub:type="index-term">test</span>, <a epub:type="index-locator" href="intro.html#a001">1</a></li>
+</ul>
+<section epub:type="index-group">
+<h2>Part I</h2>
+<ul epub:type="index-entry-list">
+<li epub:type="index-entry"><span epub:type="index-term">test</span>
+<ul epub:type="index-entry-list">
+<li epub:type="index-entry"><span epub:type="index-term">unit</span>, <a epub:type="index-locator" href="ch1.html#a001">1</a></li>
+</ul>
+</li>
+<li epub:type="index-entry"><span epub:type="index-term">unit test</span>, <span epub:type="index-xref-preferred">see test, unit</span></li>
+</ul>
+</section>
+</section>
+"#;
+        assert_eq!(Epub.index_page(&index), want);
+    }
+}
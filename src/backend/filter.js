@@ -0,0 +1,42 @@
+// Filter the entries of the index page, keeping the parents and sub-entries of matching entries.
+(function () {
+    "use strict";
+    const input = document.getElementById("index-filter");
+    if (!input) {
+        return;
+    }
+    const entries = Array.from(document.querySelectorAll(".index-entry"));
+    const paragraphs = Array.from(new Set(entries.map((entry) => entry.parentElement)));
+    input.hidden = false;
+    input.addEventListener("input", () => {
+        const query = input.value.trim().toLowerCase();
+        const matches = entries.map((entry) => entry.dataset.term.includes(query));
+        const visible = matches.slice();
+        // Track the parents of each entry, to show the sub-entries of a match and the parents of a visible entry.
+        const parents = [];
+        entries.forEach((entry, idx) => {
+            parents.length = Number(entry.dataset.level) - 1;
+            if (parents.some((parent) => matches[parent])) {
+                visible[idx] = true;
+            }
+            if (visible[idx]) {
+                parents.forEach((parent) => {
+                    visible[parent] = true;
+                });
+            }
+            parents.push(idx);
+        });
+        entries.forEach((entry, idx) => {
+            entry.hidden = !visible[idx];
+        });
+        // Hide groups of entries (and their headings) that have no visible entries.
+        for (const paragraph of paragraphs) {
+            const empty = Array.from(paragraph.querySelectorAll(".index-entry")).every((entry) => entry.hidden);
+            paragraph.hidden = empty;
+            const heading = paragraph.previousElementSibling;
+            if (heading && heading.tagName === "H2") {
+                heading.hidden = empty;
+            }
+        }
+    });
+})();
@@ -0,0 +1,805 @@
+//! Backend for HTML output, which emits anchors and a generated index page.
+
+use super::{IndexBackend, Marker};
+use crate::{
+    group_by_part, locator_runs, nav_placeholder, plain_text, relative_link, Backlinks, ByPart,
+    Index, Location, Section, SubEntries, MD_LINK_RE,
+};
+use regex::Regex;
+use std::{borrow::Borrow, collections::HashSet, path::Path, sync::LazyLock};
+
+/// Indentation to use for a nest-under entry, e.g.:
+///
+///   testing,
+///        fuzz testing
+///   ^^^^^
+const NEST_UNDER_INDENT: &str = "&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;";
+
+/// Indentation to use for use-chapter-names entries, e.g.:
+///
+///   testing
+///        Introduction,
+///        Tooling,
+///   ^^^^^
+const USE_NAMES_INDENT: &str = "&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;";
+
+/// Text of the marker that links from an occurrence of index markup back to the index.
+const BACKLINK_MARKER: &str = "↩";
+
+/// Regular expression to match the anchor that identifies an entry in the index page.
+static ENTRY_ID_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"<a name="index-[^"]*"></a>"#).unwrap());
+
+/// Script that filters the entries of the index page.
+const FILTER_SCRIPT: &str = include_str!("filter.js");
+
+/// Backend that emits HTML anchors, and generates an index page that links to them.
+pub(crate) struct Html;
+
+impl IndexBackend for Html {
+    fn collects_locations(&self) -> bool {
+        true
+    }
+
+    fn escape(&self, text: &str) -> String {
+        // Index text is emitted as Markdown, so needs no escaping.
+        text.to_string()
+    }
+
+    fn marker(&self, index: &Index, marker: &Marker) -> String {
+        let anchor = marker.anchor.as_deref().unwrap_or_default();
+        let mut prefix = format!("<a name=\"{anchor}\"></a>");
+        // Anchors are only unique within a chapter file, so draft chapters (which have no file) get no navigation.
+        if index.occurrence_nav && marker.path.is_some() {
+            // Navigation has to immediately follow the anchor, so that it can be shown when the anchor is targeted.
+            prefix += &nav_placeholder(anchor);
+        }
+        let text = marker.text();
+        match index.backlink(marker) {
+            // Links can't be nested, so only link text that doesn't already include a link.
+            Some(href)
+                if index.backlinks == Backlinks::Link
+                    && marker.visible
+                    && !MD_LINK_RE.is_match(marker.content) =>
+            {
+                format!("{prefix}[{text}]({href})")
+            }
+            Some(href) => {
+                format!("{prefix}{text}<sup>[{BACKLINK_MARKER}]({href})</sup>")
+            }
+            None => format!("{prefix}{text}"),
+        }
+    }
+
+    fn index_page(&self, index: &Index) -> String {
+        let mut result = String::new();
+        result += &format!("# {}\n\n", index.strings.title);
+
+        if index.filter {
+            // The filter box is only shown if the script runs.
+            result += &format!(
+                "<input type=\"search\" id=\"index-filter\" placeholder=\"{0}\" aria-label=\"{0}\" hidden>\n\n",
+                attribute(&index.strings.filter)
+            );
+        }
+
+        if index.by_part == ByPart::Sections {
+            for part in index.indexed_parts() {
+                if let Some(part) = &part {
+                    result += &format!("\n## {part}\n\n");
+                }
+                result = self.append_groups(index, result, Some(part.as_deref()));
+            }
+            // An entry can be in more than one section, but links to it go to the first.
+            let mut seen = HashSet::new();
+            result = ENTRY_ID_RE
+                .replace_all(&result, |caps: &regex::Captures| {
+                    if seen.insert(caps[0].to_string()) {
+                        caps[0].to_string()
+                    } else {
+                        String::new()
+                    }
+                })
+                .to_string();
+        } else {
+            result = self.append_groups(index, result, None);
+        }
+
+        if index.filter {
+            // The script runs as soon as it is parsed, so it comes after the entries that it filters.
+            result += &format!("\n<script>\n{FILTER_SCRIPT}</script>\n");
+        }
+        result
+    }
+
+    fn glossary_page(&self, index: &Index) -> String {
+        let from = index.glossary_path.borrow().clone();
+        let index_path = index.index_path.borrow().clone();
+        let mut result = format!("# {}\n\n", index.strings.glossary);
+        for (term, definition) in index.glossary_entries() {
+            result += &format!(
+                "<a name=\"{}\"></a>**{term}**:",
+                index.anchor_id("glossary-", &term)
+            );
+            if let Some(definition) = definition {
+                result += &format!(" {definition}");
+            }
+            if let Some(path) = &index_path {
+                if index.entries.borrow().contains_key(&term) {
+                    result += &format!(
+                        " ([{}]({}#{}))",
+                        index.strings.title,
+                        relative_link(from.as_deref(), path),
+                        index.anchor_id("index-", &term)
+                    );
+                }
+            }
+            result += "\n\n";
+        }
+        result
+    }
+
+    fn scoped_index(
+        &self,
+        index: &Index,
+        entries: &[(String, Vec<Location>)],
+        from: Option<&Path>,
+    ) -> String {
+        let mut result = String::new();
+        for (entry, locations) in entries {
+            result += index.display(entry);
+            if !locations.is_empty() {
+                result += ", ";
+                result += &self.locators(index, locations, from);
+            }
+            result += "<br/>\n";
+        }
+        result
+    }
+}
+
+impl Html {
+    /// Generate the locator for the `idx`-th location of an entry, linking from the chapter at `from`.
+    fn locator(&self, index: &Index, idx: usize, loc: &Location, from: Option<&Path>) -> String {
+        let text = if index.use_chapter_names {
+            loc.name.to_string()
+        } else {
+            format!("{}", idx + 1)
+        };
+        match &loc.path {
+            Some(path) if index.previews && !loc.snippet.is_empty() => format!(
+                "[{text}]({}#{} \"{}\")",
+                relative_link(from, path),
+                loc.anchor,
+                loc.snippet.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+            Some(path) => format!("[{text}]({}#{})", relative_link(from, path), loc.anchor),
+            None => text,
+        }
+    }
+
+    /// Return the comma-separated locators for a list of locations, with runs of locations in the same chapter shown
+    /// as a range if enabled (and locators are not chapter names).
+    fn locators<L: Borrow<Location>>(
+        &self,
+        index: &Index,
+        locations: &[L],
+        from: Option<&Path>,
+    ) -> String {
+        let locator = |idx: usize| self.locator(index, idx, locations[idx].borrow(), from);
+        if !index.locator_ranges || index.use_chapter_names {
+            return (0..locations.len())
+                .map(locator)
+                .collect::<Vec<_>>()
+                .join(", ");
+        }
+        locator_runs(locations)
+            .into_iter()
+            .map(|(first, last)| {
+                if first == last {
+                    locator(first)
+                } else {
+                    format!("{}{}{}", locator(first), index.strings.range, locator(last))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Append the entries of the generated index that have locations in `section`, under their group headings.
+    fn append_groups(&self, index: &Index, mut result: String, section: Section) -> String {
+        // Group headings go beneath the heading of the section, if there is one.
+        let heading = if section.is_some() { "###" } else { "##" };
+        let (groups, sub_entries) = index.grouped_entries(section);
+        for (label, keys) in groups {
+            if let Some(label) = label {
+                result += &format!("\n{heading} {label}\n\n");
+            }
+            for entry in keys {
+                let display = index.display(&entry);
+                result = self.append_entries(
+                    index,
+                    result,
+                    &sub_entries,
+                    section,
+                    std::slice::from_ref(&entry),
+                    display,
+                );
+            }
+        }
+        result
+    }
+
+    /// Append an entry to the generated index, followed by its sub-entries.
+    fn append_entries(
+        &self,
+        index: &Index,
+        mut result: String,
+        sub_entries: &SubEntries,
+        section: Section,
+        chain: &[String],
+        entry_display: &str,
+    ) -> String {
+        let entry = chain.last().expect("chain of entries is not empty");
+        let level = chain.len();
+        let indent = NEST_UNDER_INDENT.repeat(level - 1);
+        if index.filter {
+            // Mark up each entry with what the filter needs to know about it.
+            result += &format!(
+                "<span class=\"index-entry\" data-level=\"{level}\" data-term=\"{}\">",
+                attribute(&plain_text(entry).to_lowercase())
+            );
+        }
+        if index.is_placed(chain) {
+            result = self.append_entry(index, result, section, &indent, entry, entry_display);
+        } else {
+            // Only a heading for the sub-entries, which are placed under it.
+            result += &indent;
+            result += entry_display;
+        }
+        result += "<br/>";
+        if index.filter {
+            result += "</span>";
+        }
+        result += "\n";
+        if let Some(subs) = sub_entries.get(chain) {
+            for sub in subs.iter() {
+                let mut sub_chain = chain.to_vec();
+                sub_chain.push(sub.clone());
+                result = self.append_entries(
+                    index,
+                    result,
+                    sub_entries,
+                    section,
+                    &sub_chain,
+                    index.subentry(entry, sub),
+                );
+            }
+        }
+        result
+    }
+
+    /// Append an entry to the generated index.
+    fn append_entry(
+        &self,
+        index: &Index,
+        mut result: String,
+        section: Section,
+        indent: &str,
+        entry: &str,
+        entry_display: &str,
+    ) -> String {
+        result += indent;
+        if index.has_entry_ids() {
+            // Give each entry an identifier that other pages can link to.
+            result += &format!("<a name=\"{}\"></a>", index.anchor_id("index-", entry));
+        }
+        let entry_display = match index.links.get(entry) {
+            Some(link) => format!("[{entry_display}]({link})"),
+            None => entry_display.to_string(),
+        };
+        if let Some(alt) = index.see_instead.get(entry) {
+            result += &format!("{}, {}", entry_display, index.strings.see(alt));
+            // Check that the destination exists.
+            if index.entries.borrow().get(alt).is_none() {
+                log::error!(
+                    "Destination of see_instead '{}' => '{}' not in index!",
+                    entry,
+                    alt
+                );
+            }
+        } else {
+            result += &entry_display;
+            // An entry that other entries are only nested under has no locations of its own, so is just a heading.
+            if let Some(locations) = index.section_locations(entry, section) {
+                if index.by_part == ByPart::Locators {
+                    // e.g. "entry, Part I: 1, 2; Part II: 1"
+                    for (num, (part, locations)) in
+                        group_by_part(&locations).into_iter().enumerate()
+                    {
+                        if index.use_chapter_names {
+                            result += &format!(",<br/>\n{indent}{USE_NAMES_INDENT}");
+                        } else if num == 0 {
+                            result += ", ";
+                        } else {
+                            result += "; ";
+                        }
+                        if let Some(part) = part {
+                            result += &format!("{part}: ");
+                        }
+                        result += &self.locators(index, &locations, None);
+                    }
+                } else if index.use_chapter_names {
+                    for (idx, loc) in locations.iter().enumerate() {
+                        result += &format!(",<br/>\n{indent}{USE_NAMES_INDENT}");
+                        result += &self.locator(index, idx, loc, None);
+                    }
+                } else if !locations.is_empty() {
+                    result += ", ";
+                    result += &self.locators(index, &locations, None);
+                }
+            }
+        }
+        if let Some(path) = index.glossary_path.borrow().as_ref() {
+            if index.in_glossary(entry) {
+                let from = index.index_path.borrow().clone();
+                result += &format!(
+                    ", [{}]({}#{})",
+                    index.strings.glossary,
+                    relative_link(from.as_deref(), path),
+                    index.anchor_id("glossary-", entry)
+                );
+            }
+        }
+        if let Some(see_also) = index.see_also.get(entry) {
+            result += &format!(", {}", index.strings.see_also(&see_also.join(", ")));
+            // Check that the destinations exist.
+            for alt in see_also {
+                if index.entries.borrow().get(alt).is_none() {
+                    log::error!(
+                        "Destination of see_also '{}' => '{}' not in index!",
+                        entry,
+                        alt
+                    );
+                }
+            }
+        }
+        if let Some(description) = index.descriptions.get(entry) {
+            result += &format!(" {}", index.strings.description(description));
+        }
+        result
+    }
+}
+
+/// Escape text for inclusion in an HTML attribute value.
+fn attribute(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strings::Strings;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_deep_nesting() {
+        let index = Index {
+            nest_under: HashMap::from([
+                ("b".to_string(), "a".to_string()),
+                ("c".to_string(), "b".to_string()),
+                ("d".to_string(), "c".to_string()),
+                ("e".to_string(), "d".to_string()),
+            ]),
+            ..Default::default()
+        };
+        index.process_chapter(
+            &Html,
+            &Some(PathBuf::from("ch1.md")),
+            None,
+            "Chapter",
+            "{{i:a}} {{i:b}} {{i:c}} {{i:d}} {{i:e}}",
+        );
+        // Entries below the maximum depth are lifted up to the deepest level, and appear only once.
+        let got = Html.index_page(&index);
+        let want = "# Index
+
+a, [1](ch1.md#a001)<br/>
+&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;b, [1](ch1.md#a002)<br/>
+&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;c, [1](ch1.md#a003)<br/>
+&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;d, [1](ch1.md#a004)<br/>
+&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;e, [1](ch1.md#a005)<br/>
+";
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn test_index_page() {
+        let index = Index {
+            see_instead: HashMap::from([("unit test".to_string(), "test, unit".to_string())]),
+            see_also: HashMap::from([("test".to_string(), vec!["coverage".to_string()])]),
+            nest_under: HashMap::from([
+                ("test, unit".to_string(), "test".to_string()),
+                ("test, unit, doc".to_string(), "test, unit".to_string()),
+            ]),
+            suppress_head: true,
+            ..Default::default()
+        };
+        index.process_chapter(
+            &Html,
+            &Some(PathBuf::from("ch1.md")),
+            None,
+            "Chapter",
+            "{{i:test}} {{i:unit test}} {{hi:test, unit, doc}} {{i:coverage}} {{i:test}}",
+        );
+        let want = "# Index
+
+coverage, [1](ch1.md#a004)<br/>
+test, [1](ch1.md#a001), [2](ch1.md#a005), see also coverage<br/>
+&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;unit, [1](ch1.md#a002)<br/>
+&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;doc, [1](ch1.md#a003)<br/>
+unit test, see test, unit<br/>
+";
+        assert_eq!(Html.index_page(&index), want);
+    }
+
+    #[test]
+    fn test_locator_ranges() {
+        let mut strings = Strings::default();
+        strings.set("range", " to ");
+        let cases = [
+            (
+                false,
+                Strings::default(),
+                "borrow, [1](ch1.md#a001), [2](ch1.md#a002), [3](ch1.md#a003), [4](ch2.md#a001), \
+                 [5](ch2.md#a002)<br/>\n",
+            ),
+            (
+                true,
+                Strings::default(),
+                "borrow, [1](ch1.md#a001)–[3](ch1.md#a003), [4](ch2.md#a001), [5](ch2.md#a002)<br/>\n",
+            ),
+            (
+                true,
+                strings,
+                "borrow, [1](ch1.md#a001) to [3](ch1.md#a003), [4](ch2.md#a001), [5](ch2.md#a002)<br/>\n",
+            ),
+        ];
+        for (locator_ranges, strings, want) in cases {
+            let index = Index {
+                locator_ranges,
+                strings,
+                ..Default::default()
+            };
+            for (path, content) in [
+                ("ch1.md", "{{i:borrow}} {{i:borrow}} {{i:borrow}}"),
+                ("ch2.md", "{{i:borrow}} {{i:borrow}}"),
+            ] {
+                index.process_chapter(&Html, &Some(PathBuf::from(path)), None, "Chapter", content);
+            }
+            let got = Html.index_page(&index);
+            assert!(got.ends_with(want), "Mismatch for range: {}", got);
+        }
+    }
+
+    #[test]
+    fn test_entry_descriptions() {
+        let index = Index {
+            aliases: HashMap::from([("borrowck".to_string(), "borrow checker".to_string())]),
+            displays: HashMap::from([
+                (
+                    "borrow checker".to_string(),
+                    "borrow checker, the".to_string(),
+                ),
+                ("borrowing, mutable".to_string(), "mutably".to_string()),
+            ]),
+            links: HashMap::from([("borrowing".to_string(), "https://example.com".to_string())]),
+            descriptions: HashMap::from([(
+                "borrow checker".to_string(),
+                "Checks references.".to_string(),
+            )]),
+            nest_under: HashMap::from([(
+                "borrowing, mutable".to_string(),
+                "borrowing".to_string(),
+            )]),
+            ..Default::default()
+        };
+        let got = index.process_chapter(
+            &Html,
+            &Some(PathBuf::from("ch1.md")),
+            None,
+            "Chapter",
+            "{{i:borrowck}} {{i:borrow checker}} {{i:borrowing}} {{hi:borrowing, mutable}}",
+        );
+        assert!(got.starts_with("<a name=\"a001\"></a>borrowck "));
+        let want = "# Index
+
+borrow checker, the, [1](ch1.md#a001), [2](ch1.md#a002) — Checks references.<br/>
+[borrowing](https://example.com), [1](ch1.md#a003)<br/>
+&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;mutably, [1](ch1.md#a004)<br/>
+";
+        assert_eq!(Html.index_page(&index), want);
+    }
+
+    #[test]
+    fn test_filter() {
+        let index = Index {
+            filter: true,
+            nest_under: HashMap::from([("test, unit".to_string(), "test".to_string())]),
+            ..Default::default()
+        };
+        index.process_chapter(
+            &Html,
+            &Some(PathBuf::from("ch1.md")),
+            None,
+            "Chapter",
+            "{{i:test}} {{i:test, unit}} {{i:`\"quoted\"`}}",
+        );
+        let got = Html.index_page(&index);
+        let want = "# Index
+
+<input type=\"search\" id=\"index-filter\" placeholder=\"Filter entries\" aria-label=\"Filter entries\" hidden>
+
+<span class=\"index-entry\" data-level=\"1\" data-term=\"&quot;quoted&quot;\">`\"quoted\"`, [1](ch1.md#a003)<br/></span>
+<span class=\"index-entry\" data-level=\"1\" data-term=\"test\">test, [1](ch1.md#a001)<br/></span>
+<span class=\"index-entry\" data-level=\"2\" data-term=\"test, unit\">&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;test, unit, [1](ch1.md#a002)<br/></span>
+
+<script>
+";
+        assert!(got.starts_with(want), "Unexpected index page: {}", got);
+        // The script must come after all of the entries that it filters.
+        let script = got.find("<script>").unwrap();
+        assert!(got.rfind("class=\"index-entry\"").unwrap() < script);
+        assert!(
+            got.ends_with("</script>\n"),
+            "Unexpected index page: {}",
+            got
+        );
+    }
+
+    #[test]
+    fn test_backlinks() {
+        let input = "{{i:borrow}} {{hi:move}} {{ii:[Box](https://x)}} {{i:borrowing}}";
+        let cases = [
+            (
+                Backlinks::Link,
+                "<a name=\"a001\"></a>[borrow](../indexing.md#index-borrow) \
+                 <a name=\"a002\"></a><sup>[↩](../indexing.md#index-move)</sup> \
+                 <a name=\"a003\"></a>*[Box](https://x)*<sup>[↩](../indexing.md#index-Box)</sup> \
+                 <a name=\"a004\"></a>[borrowing](../indexing.md#index-borrow)",
+            ),
+            (
+                Backlinks::Marker,
+                "<a name=\"a001\"></a>borrow<sup>[↩](../indexing.md#index-borrow)</sup> \
+                 <a name=\"a002\"></a><sup>[↩](../indexing.md#index-move)</sup> \
+                 <a name=\"a003\"></a>*[Box](https://x)*<sup>[↩](../indexing.md#index-Box)</sup> \
+                 <a name=\"a004\"></a>borrowing<sup>[↩](../indexing.md#index-borrow)</sup>",
+            ),
+        ];
+        for (backlinks, want) in cases {
+            let index = Index {
+                backlinks,
+                see_instead: HashMap::from([("borrowing".to_string(), "borrow".to_string())]),
+                index_path: RefCell::new(Some(PathBuf::from("indexing.md"))),
+                ..Default::default()
+            };
+            let got = index.process_chapter(
+                &Html,
+                &Some(PathBuf::from("part1/ch1.md")),
+                None,
+                "Chapter",
+                input,
+            );
+            let got = index.resolve_backlinks(&got);
+            assert_eq!(got, want, "Mismatch for backlinks: {:?}", backlinks);
+            assert!(Html
+                .index_page(&index)
+                .contains("<a name=\"index-borrow\"></a>borrow, [1](part1/ch1.md#a001)"));
+        }
+    }
+
+    #[test]
+    fn test_colliding_ids() {
+        let index = Index {
+            backlinks: Backlinks::Marker,
+            index_path: RefCell::new(Some(PathBuf::from("indexing.md"))),
+            ..Default::default()
+        };
+        let got = index.process_chapter(
+            &Html,
+            &Some(PathBuf::from("ch1.md")),
+            None,
+            "Chapter",
+            "{{hi:`&mut`}}{{hi:mut}}{{hi:`*`}}{{hi:`_`}}",
+        );
+        let got = index.resolve_backlinks(&got);
+        let cases = [
+            ("a001", "index-mut"),
+            ("a002", "index-mut-2"),
+            ("a003", "index"),
+            ("a004", "index-2"),
+        ];
+        let page = Html.index_page(&index);
+        for (anchor, want) in cases {
+            assert!(
+                got.contains(&format!(
+                    "<a name=\"{anchor}\"></a><sup>[↩](indexing.md#{want})</sup>"
+                )),
+                "Mismatch for anchor {}: {}",
+                anchor,
+                got
+            );
+            // The entry with the identifier is the one whose location is the anchor.
+            assert!(
+                page.lines()
+                    .any(|line| line.starts_with(&format!("<a name=\"{want}\"></a>"))
+                        && line.contains(&format!("(ch1.md#{anchor})"))),
+                "Unexpected index page for anchor {}: {}",
+                anchor,
+                page
+            );
+        }
+    }
+
+    #[test]
+    fn test_previews() {
+        let index = Index {
+            previews: true,
+            ..Default::default()
+        };
+        index.process_chapter(
+            &Html,
+            &Some(PathBuf::from("ch1.md")),
+            None,
+            "Chapter",
+            "A \"{{i:borrow}}\" is short.",
+        );
+        let want = "# Index
+
+borrow, [1](ch1.md#a001 \"A \\\"borrow\\\" is short.\")<br/>
+";
+        assert_eq!(Html.index_page(&index), want);
+    }
+
+    #[test]
+    fn test_glossary() {
+        let index = Index {
+            glossary: HashMap::from([
+                ("borrow".to_string(), "Take a reference.".to_string()),
+                ("lifetime".to_string(), "Scope of validity.".to_string()),
+            ]),
+            index_path: RefCell::new(Some(PathBuf::from("indexing.md"))),
+            glossary_path: RefCell::new(Some(PathBuf::from("appendix/glossary.md"))),
+            ..Default::default()
+        };
+        let got = index.process_chapter(
+            &Html,
+            &Some(PathBuf::from("ch1.md")),
+            None,
+            "Chapter",
+            "{{g:borrow}} {{g:move}} {{i:test}}",
+        );
+        assert_eq!(
+            got,
+            "<a name=\"a001\"></a>borrow <a name=\"a002\"></a>move <a name=\"a003\"></a>test"
+        );
+        let want = "# Index
+
+<a name=\"index-borrow\"></a>borrow, [1](ch1.md#a001), [Glossary](appendix/glossary.md#glossary-borrow)<br/>
+<a name=\"index-move\"></a>move, [1](ch1.md#a002), [Glossary](appendix/glossary.md#glossary-move)<br/>
+<a name=\"index-test\"></a>test, [1](ch1.md#a003)<br/>
+";
+        assert_eq!(Html.index_page(&index), want);
+        let want = "# Glossary
+
+<a name=\"glossary-borrow\"></a>**borrow**: Take a reference. ([Index](../indexing.md#index-borrow))
+
+<a name=\"glossary-lifetime\"></a>**lifetime**: Scope of validity.
+
+<a name=\"glossary-move\"></a>**move**: ([Index](../indexing.md#index-move))
+
+";
+        assert_eq!(Html.glossary_page(&index), want);
+    }
+
+    #[test]
+    fn test_index_page_by_part() {
+        let chapters = [
+            ("intro.md", None, "{{i:test}}"),
+            (
+                "ch1.md",
+                Some("Part I"),
+                "{{i:test}} {{i:test}} {{i:coverage}}",
+            ),
+            ("ch2.md", Some("Part II"), "{{i:test}}"),
+        ];
+        let cases = [
+            (
+                ByPart::Locators,
+                "# Index
+
+coverage, Part I: [1](ch1.md#a003)<br/>
+test, [1](intro.md#a001); Part I: [1](ch1.md#a001), [2](ch1.md#a002); Part II: [1](ch2.md#a001)<br/>
+",
+            ),
+            (
+                ByPart::Sections,
+                "# Index
+
+test, [1](intro.md#a001)<br/>
+
+## Part I
+
+coverage, [1](ch1.md#a003)<br/>
+test, [1](ch1.md#a001), [2](ch1.md#a002)<br/>
+
+## Part II
+
+test, [1](ch2.md#a001)<br/>
+",
+            ),
+        ];
+        for (by_part, want) in cases {
+            let index = Index {
+                by_part,
+                parts: RefCell::new(vec!["Part I".to_string(), "Part II".to_string()]),
+                ..Default::default()
+            };
+            for (path, part, content) in chapters {
+                index.process_chapter(&Html, &Some(PathBuf::from(path)), part, "Chapter", content);
+            }
+            assert_eq!(
+                Html.index_page(&index),
+                want,
+                "Mismatch for by_part: {:?}",
+                by_part
+            );
+        }
+    }
+
+    #[test]
+    fn test_index_page_sections() {
+        let index = Index {
+            by_part: ByPart::Sections,
+            backlinks: Backlinks::Marker,
+            see_instead: HashMap::from([("borrowck".to_string(), "borrow checker".to_string())]),
+            see_also: HashMap::from([("borrow".to_string(), vec!["move".to_string()])]),
+            nest_under: HashMap::from([("borrow, mutable".to_string(), "borrow".to_string())]),
+            descriptions: HashMap::from([("move".to_string(), "Transfers ownership.".to_string())]),
+            suppress_head: true,
+            index_path: RefCell::new(Some(PathBuf::from("indexing.md"))),
+            parts: RefCell::new(vec!["Part I".to_string()]),
+            ..Default::default()
+        };
+        let chapters = [
+            ("intro.md", None, "{{i:borrow}} {{i:move}}"),
+            (
+                "ch1.md",
+                Some("Part I"),
+                "{{i:borrow, mutable}} {{i:borrowck}} {{i:borrow}}",
+            ),
+        ];
+        for (path, part, content) in chapters {
+            index.process_chapter(&Html, &Some(PathBuf::from(path)), part, "Chapter", content);
+        }
+        // Each section has the full structure of the index, and only the first section that an entry is in gives
+        // it an identifier.
+        let want = "# Index
+
+<a name=\"index-borrow\"></a>borrow, [1](intro.md#a001), see also move<br/>
+<a name=\"index-move\"></a>move, [1](intro.md#a002) — Transfers ownership.<br/>
+
+## Part I
+
+borrow, [1](ch1.md#a003), see also move<br/>
+&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;<a name=\"index-borrow-mutable\"></a>mutable, [1](ch1.md#a001)<br/>
+<a name=\"index-borrow-checker\"></a>borrow checker, [1](ch1.md#a002)<br/>
+<a name=\"index-borrowck\"></a>borrowck, see borrow checker<br/>
+";
+        assert_eq!(Html.index_page(&index), want);
+    }
+}
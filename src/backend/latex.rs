@@ -0,0 +1,206 @@
+//! Backend for LaTeX output, for use with `makeindex`.
+
+use super::{
+    pandoc::{raw_block, raw_inline},
+    IndexBackend, Marker,
+};
+use crate::Index;
+
+/// LaTeX command that prints the index catalog.
+pub(super) const PRINT_INDEX: &str = "\\printindex";
+
+/// Backend that emits LaTeX `\index{}` commands.
+pub(crate) struct Latex;
+
+impl Latex {
+    /// Generate the LaTeX `\index{}` argument for an entry, including any nesting.
+    fn entry(&self, index: &Index, entry: &str) -> String {
+        index
+            .levels(entry)
+            .into_iter()
+            .zip(index.level_displays(entry))
+            .map(|(level, display)| match index.sort_as.get(level) {
+                // An explicit sort key takes the place of the one derived from the text.
+                Some(sort) => format!("{}@{}", latex_escape(sort), text_to_latex(display).1),
+                None => latex_key(display),
+            })
+            .collect::<Vec<_>>()
+            .join("!")
+    }
+
+    /// Generate the bare LaTeX `\index{}` commands for index markup, without the text of the markup.
+    pub(super) fn commands(&self, index: &Index, marker: &Marker) -> String {
+        let mut result = format!("\\index{{{}}}", self.entry(index, marker.target));
+        if marker.target != marker.entry {
+            // Also emit the see-reference from the original entry.
+            result += &format!(
+                "\\index{{{}|see{{{}}}}}",
+                latex_key(marker.entry),
+                text_to_latex(marker.target).1
+            );
+        }
+        if let Some(see_also) = index.see_also.get(marker.target) {
+            let see_also = see_also
+                .iter()
+                .map(|alt| text_to_latex(alt).1)
+                .collect::<Vec<_>>()
+                .join(", ");
+            result += &format!(
+                "\\index{{{}|seealso{{{see_also}}}}}",
+                self.entry(index, marker.target)
+            );
+        }
+        log::debug!("latex entry '{result}'");
+        result
+    }
+}
+
+impl IndexBackend for Latex {
+    fn escape(&self, text: &str) -> String {
+        latex_escape(text)
+    }
+
+    fn marker(&self, index: &Index, marker: &Marker) -> String {
+        // The commands are emitted as a raw inline, as otherwise Markdown parsing would treat
+        // escapes such as `\%` and `\_` as Markdown backslash escapes, and drop the backslash.
+        raw_inline(&self.commands(index, marker), "latex") + &marker.text()
+    }
+
+    fn index_page(&self, _index: &Index) -> String {
+        // LaTeX (via makeindex) takes care of generating the index catalog.
+        raw_block(PRINT_INDEX, "latex")
+    }
+}
+
+/// Convert index text into a form suitable for LaTeX, returning `(sort_key, display)`.
+///
+/// Code spans are rendered with `\texttt{}`, and an entry surrounded by Markdown emphasis is rendered with `\emph{}`.
+fn text_to_latex(text: &str) -> (String, String) {
+    let plain = text.trim_matches('*').trim_matches('_');
+    let emphasized = plain.len() != text.len();
+    let mut sort_key = String::new();
+    let mut display = String::new();
+    for (idx, part) in plain.split('`').enumerate() {
+        let escaped = latex_escape(part);
+        sort_key += &escaped;
+        if idx % 2 == 1 {
+            display += &format!("\\texttt{{{escaped}}}");
+        } else {
+            display += &escaped;
+        }
+    }
+    if emphasized {
+        display = format!("\\emph{{{display}}}");
+    }
+    (sort_key, display)
+}
+
+/// Convert index text into a single level of a LaTeX `\index{}` argument, using the `sort@display` form when the
+/// display form includes formatting.
+fn latex_key(text: &str) -> String {
+    let (sort_key, display) = text_to_latex(text);
+    if sort_key == display {
+        display
+    } else {
+        format!("{sort_key}@{display}")
+    }
+}
+
+/// Escape characters that are special to LaTeX or to `makeindex`.
+fn latex_escape(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        match c {
+            '\\' => result += "\\textbackslash{}",
+            '~' => result += "\\textasciitilde{}",
+            '^' => result += "\\textasciicircum{}",
+            '{' | '}' | '#' | '$' | '%' | '&' | '_' => {
+                result.push('\\');
+                result.push(c);
+            }
+            // `makeindex` uses `"` to quote its own special characters.
+            '!' | '@' | '|' | '"' => {
+                result.push('"');
+                result.push(c);
+            }
+            _ => result.push(c),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_latex_key() {
+        let cases = [
+            ("abc", "abc"),
+            ("`code`", "code@\\texttt{code}"),
+            ("*italics*", "italics@\\emph{italics}"),
+            ("_emphasis_", "emphasis@\\emph{emphasis}"),
+            ("`*`", "*@\\texttt{*}"),
+            ("a_b", "a\\_b"),
+            ("50%", "50\\%"),
+            ("R&D", "R\\&D"),
+            ("a\\b", "a\\textbackslash{}b"),
+            ("{x}", "\\{x\\}"),
+            ("wow!", "wow\"!"),
+            ("a@b", "a\"@b"),
+            ("a|b", "a\"|b"),
+            ("`#[cfg]`", "\\#[cfg]@\\texttt{\\#[cfg]}"),
+        ];
+        for (input, want) in cases {
+            let got = latex_key(input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_marker() {
+        let index = Index::default();
+        let cases = [
+            ("borrowck", "`\\index{borrowck}`{=latex}borrowck"),
+            ("50%", "`\\index{50\\%}`{=latex}50%"),
+            ("`a_b`", "`\\index{a\\_b@\\texttt{a\\_b}}`{=latex}`a_b`"),
+        ];
+        for (input, want) in cases {
+            let marker = Marker {
+                path: None,
+                content: input,
+                entry: input,
+                target: input,
+                visible: true,
+                italic: false,
+                anchor: None,
+            };
+            let got = Latex.marker(&index, &marker);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_sort_as() {
+        let index = Index {
+            sort_as: HashMap::from([
+                ("`&mut`".to_string(), "mut".to_string()),
+                ("Müller".to_string(), "Mueller".to_string()),
+            ]),
+            nest_under: HashMap::from([("`&mut`, slice".to_string(), "`&mut`".to_string())]),
+            suppress_head: true,
+            ..Default::default()
+        };
+        let cases = [
+            ("`&mut`", "mut@\\texttt{\\&mut}"),
+            ("`&mut`, slice", "mut@\\texttt{\\&mut}!slice"),
+            ("Müller", "Mueller@Müller"),
+            ("other", "other"),
+        ];
+        for (input, want) in cases {
+            let got = Latex.entry(&index, input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+}
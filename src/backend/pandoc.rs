@@ -0,0 +1,147 @@
+//! Backend for Pandoc-based renderers, emitting index terms as Pandoc raw content for each supported output format.
+
+use super::{
+    docbook::DocBook,
+    latex::{Latex, PRINT_INDEX},
+    IndexBackend, Marker,
+};
+use crate::Index;
+
+/// Backend that emits raw DocBook, LaTeX and Office Open XML (DOCX) index terms, using Pandoc's `raw_attribute`
+/// syntax so that each output format only includes its own index terms.
+pub(crate) struct Pandoc;
+
+impl Pandoc {
+    /// Generate a Word `XE` field for an entry, with an optional cross-reference.
+    fn xe_field(&self, index: &Index, entry: &str, xref: Option<&str>) -> String {
+        // Word uses a colon to separate the levels of a nested entry.
+        let levels = index
+            .level_displays(entry)
+            .into_iter()
+            .map(|level| self.field_text(level))
+            .collect::<Vec<_>>()
+            .join(":");
+        let mut instr = format!(" XE \"{levels}\" ");
+        if let Some(xref) = xref {
+            instr += &format!("\\t \"{}\" ", self.field_text(xref));
+        }
+        format!(
+            "<w:r><w:fldChar w:fldCharType=\"begin\"/></w:r>\
+             <w:r><w:instrText xml:space=\"preserve\">{}</w:instrText></w:r>\
+             <w:r><w:fldChar w:fldCharType=\"end\"/></w:r>",
+            self.escape(&instr)
+        )
+    }
+
+    /// Convert index text into a form suitable for inclusion in a quoted Word field argument.
+    fn field_text(&self, text: &str) -> String {
+        text.replace('`', "")
+            .trim_matches('*')
+            .trim_matches('_')
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace(':', "\\:")
+    }
+}
+
+impl IndexBackend for Pandoc {
+    fn escape(&self, text: &str) -> String {
+        DocBook.escape(text)
+    }
+
+    fn marker(&self, index: &Index, marker: &Marker) -> String {
+        // Generate the index terms for each format without the text of the markup, which is
+        // emitted (once) as normal Markdown.
+        let hidden = Marker {
+            visible: false,
+            ..marker.clone()
+        };
+        let mut result = raw_inline(&DocBook.marker(index, &hidden), "docbook");
+        result += &raw_inline(&Latex.commands(index, marker), "latex");
+
+        let mut fields = self.xe_field(index, marker.target, None);
+        if marker.target != marker.entry {
+            let see = index.strings.see(marker.target);
+            fields += &self.xe_field(index, marker.entry, Some(&see));
+        }
+        if let Some(see_also) = index.see_also.get(marker.target) {
+            let see_also = index.strings.see_also(&see_also.join(", "));
+            fields += &self.xe_field(index, marker.target, Some(&see_also));
+        }
+        result += &raw_inline(&fields, "openxml");
+        result + &marker.text()
+    }
+
+    fn index_page(&self, index: &Index) -> String {
+        // The downstream toolchain takes care of generating the index catalog.
+        let docx_index = "<w:p><w:r><w:fldChar w:fldCharType=\"begin\"/></w:r>\
+                          <w:r><w:instrText xml:space=\"preserve\"> INDEX </w:instrText></w:r>\
+                          <w:r><w:fldChar w:fldCharType=\"separate\"/></w:r>\
+                          <w:r><w:fldChar w:fldCharType=\"end\"/></w:r></w:p>";
+        [
+            raw_block(&DocBook.index_page(index), "docbook"),
+            raw_block(PRINT_INDEX, "latex"),
+            raw_block(docx_index, "openxml"),
+        ]
+        .join("\n")
+    }
+}
+
+/// Wrap content in a Pandoc raw inline for the given output format.
+pub(super) fn raw_inline(content: &str, format: &str) -> String {
+    // Use a run of backticks that is longer than any run in the content.
+    let longest = content
+        .split(|c| c != '`')
+        .map(|run| run.len())
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat(longest + 1);
+    if longest > 0 {
+        format!("{fence} {content} {fence}{{={format}}}")
+    } else {
+        format!("{fence}{content}{fence}{{={format}}}")
+    }
+}
+
+/// Wrap content in a Pandoc raw block for the given output format.
+pub(super) fn raw_block(content: &str, format: &str) -> String {
+    format!("```{{={format}}}\n{}\n```\n", content.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_inline() {
+        let cases = [
+            ("<index/>", "`<index/>`{=docbook}"),
+            ("a`b", "`` a`b ``{=docbook}"),
+        ];
+        for (input, want) in cases {
+            let got = raw_inline(input, "docbook");
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_marker() {
+        let index = Index::default();
+        let marker = Marker {
+            path: None,
+            content: "a:b",
+            entry: "a:b",
+            target: "a:b",
+            visible: true,
+            italic: true,
+            anchor: None,
+        };
+        let want = "`<indexterm><primary>a:b</primary></indexterm>`{=docbook}\
+                    `\\index{a:b}`{=latex}\
+                    `<w:r><w:fldChar w:fldCharType=\"begin\"/></w:r>\
+                    <w:r><w:instrText xml:space=\"preserve\"> XE &quot;a\\:b&quot; </w:instrText></w:r>\
+                    <w:r><w:fldChar w:fldCharType=\"end\"/></w:r>`{=openxml}\
+                    *a:b*";
+        assert_eq!(Pandoc.marker(&index, &marker), want);
+    }
+}
@@ -0,0 +1,21 @@
+//! Backend that passes content through, without any indexing output.
+
+use super::{IndexBackend, Marker};
+use crate::Index;
+
+/// Backend that emits no indexing content.
+pub(crate) struct Skip;
+
+impl IndexBackend for Skip {
+    fn escape(&self, text: &str) -> String {
+        text.to_string()
+    }
+
+    fn marker(&self, _index: &Index, marker: &Marker) -> String {
+        marker.text()
+    }
+
+    fn index_page(&self, _index: &Index) -> String {
+        "".to_string()
+    }
+}
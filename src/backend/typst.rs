@@ -0,0 +1,109 @@
+//! Backend for Typst output, for use with a Typst index package.
+
+use super::{
+    pandoc::{raw_block, raw_inline},
+    IndexBackend, Marker,
+};
+use crate::Index;
+
+/// Backend that emits Typst `#index()` function calls.
+pub(crate) struct Typst;
+
+impl Typst {
+    /// Generate the Typst `index()` arguments for an entry, with one argument per level of nesting.
+    fn entry(&self, index: &Index, entry: &str) -> String {
+        index
+            .level_displays(entry)
+            .into_iter()
+            .map(typst_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl IndexBackend for Typst {
+    fn escape(&self, text: &str) -> String {
+        typst_string(text)
+    }
+
+    fn marker(&self, index: &Index, marker: &Marker) -> String {
+        // A trailing semicolon ends the function call, so that following content
+        // (e.g. a `[link]`) isn't treated as an extra argument.
+        let mut result = format!("#index({});", self.entry(index, marker.target));
+        if marker.target != marker.entry {
+            result += &format!(
+                "#index({}, see: {});",
+                self.escape(marker.entry),
+                self.escape(marker.target)
+            );
+        }
+        log::debug!("typst entry '{result}'");
+        // Emit the calls as a raw inline, as otherwise Markdown parsing would treat the `\\` and `\"`
+        // string escapes as Markdown backslash escapes, and drop the backslash.
+        raw_inline(&result, "typst") + &marker.text()
+    }
+
+    fn index_page(&self, _index: &Index) -> String {
+        // The Typst index package takes care of generating the index catalog.
+        raw_block("#make-index()", "typst")
+    }
+}
+
+/// Convert index text into a quoted Typst string literal.
+fn typst_string(text: &str) -> String {
+    // Remove surrounding MarkDown formatting characters, and escape characters that are special in a string.
+    let text = text
+        .replace('`', "")
+        .trim_matches('*')
+        .trim_matches('_')
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"");
+    format!("\"{text}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marker() {
+        let index = Index::default();
+        let cases = [
+            ("abc", "`#index(\"abc\");`{=typst}abc"),
+            (
+                "a \"quote\"",
+                "`#index(\"a \\\"quote\\\"\");`{=typst}a \"quote\"",
+            ),
+            ("a\\b", "`#index(\"a\\\\b\");`{=typst}a\\b"),
+        ];
+        for (input, want) in cases {
+            let marker = Marker {
+                path: None,
+                content: input,
+                entry: input,
+                target: input,
+                visible: true,
+                italic: false,
+                anchor: None,
+            };
+            let got = Typst.marker(&index, &marker);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_typst_string() {
+        let cases = [
+            ("abc", "\"abc\""),
+            ("`code`", "\"code\""),
+            ("*italics*", "\"italics\""),
+            ("_emphasis_", "\"emphasis\""),
+            ("a \"quote\"", "\"a \\\"quote\\\"\""),
+            ("a\\b", "\"a\\\\b\""),
+        ];
+        for (input, want) in cases {
+            let got = typst_string(input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+}
@@ -0,0 +1,411 @@
+//! Ordering of index entries.
+
+use icu_collator::{Collator, CollatorOptions, Numeric, Strength};
+use icu_locid::Locale;
+use serde::Deserialize;
+use std::cmp::Ordering;
+
+/// Value of the `collation` config option that selects plain codepoint ordering.
+pub(crate) const CODEPOINT: &str = "codepoint";
+
+/// Characters that are ignored when sorting entries by default.
+pub(crate) const DEFAULT_IGNORE_CHARS: &str = "_*{}`[]@'";
+
+/// Separator between index text and its reading, e.g. `所有権@しょゆうけん`.
+const READING_SEPARATOR: char = '@';
+
+/// Rows of the Japanese syllabary (in hiragana), with the label for each row.
+const KANA_ROWS: &[(&str, &str)] = &[
+    ("あ行", "ぁあぃいぅうぇえぉおゔ"),
+    ("か行", "かがきぎくぐけげこごゕゖ"),
+    ("さ行", "さざしじすずせぜそぞ"),
+    ("た行", "ただちぢっつづてでとど"),
+    ("な行", "なにぬねの"),
+    ("は行", "はばぱひびぴふぶぷへべぺほぼぽ"),
+    ("ま行", "まみむめも"),
+    ("や行", "ゃやゅゆょよ"),
+    ("ら行", "らりるれろ"),
+    ("わ行", "ゎわゐゑをん"),
+];
+
+/// Method of comparing the sort keys of index entries.
+///
+/// All methods ignore case, so that callers can use a stable sort to get a consistent order for entries that only
+/// differ in capitalization.
+#[derive(Default)]
+pub(crate) enum Collation {
+    /// Compare lowercased Unicode codepoints.
+    #[default]
+    Codepoint,
+    /// Compare lowercased Unicode codepoints, except that runs of digits are compared by numeric value.
+    Natural,
+    /// Compare according to the collation rules for a locale.
+    Locale {
+        /// Collator for ordering entries.
+        collator: Box<Collator>,
+        /// Collator that only distinguishes base letters, for grouping entries.
+        primary: Box<Collator>,
+    },
+}
+
+impl Collation {
+    /// Create a collation for the given locale (e.g. "de" or "sv-SE"), or [`CODEPOINT`].
+    ///
+    /// If `natural` is set, runs of digits are compared by their numeric value, so that "u8" sorts before "u16".
+    pub fn new(locale: &str, natural: bool) -> Self {
+        let fallback = if natural {
+            Self::Natural
+        } else {
+            Self::Codepoint
+        };
+        if locale == CODEPOINT {
+            return fallback;
+        }
+        let parsed = match locale.parse::<Locale>() {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                log::warn!(
+                    "Failed to parse collation locale '{locale}': {e:?}, using codepoint order"
+                );
+                return fallback;
+            }
+        };
+        let collator = |strength| {
+            let mut options = CollatorOptions::new();
+            options.strength = Some(strength);
+            if natural {
+                options.numeric = Some(Numeric::On);
+            }
+            Collator::try_new(&(&parsed).into(), options).map(Box::new)
+        };
+        // Secondary strength distinguishes accents but not case.
+        match (collator(Strength::Secondary), collator(Strength::Primary)) {
+            (Ok(collator), Ok(primary)) => {
+                log::info!("Sorting index entries with collation for '{locale}'");
+                Self::Locale { collator, primary }
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                log::warn!("No collation for locale '{locale}': {e:?}, using codepoint order");
+                fallback
+            }
+        }
+    }
+
+    /// Compare two sort keys.
+    pub fn compare(&self, a: &str, b: &str) -> Ordering {
+        match self {
+            Self::Codepoint => a.to_lowercase().cmp(&b.to_lowercase()),
+            Self::Natural => natural_cmp(&a.to_lowercase(), &b.to_lowercase()),
+            Self::Locale { collator, .. } => collator.compare(a, b),
+        }
+    }
+
+    /// Indicate whether two group labels are for the same base letter.
+    pub fn same_group(&self, a: &str, b: &str) -> bool {
+        match self {
+            Self::Codepoint | Self::Natural => a.to_lowercase() == b.to_lowercase(),
+            Self::Locale { primary, .. } => primary.compare(a, b) == Ordering::Equal,
+        }
+    }
+}
+
+/// Where entries that start with punctuation (e.g. `&mut` or `#[cfg]`) are filed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Symbols {
+    /// Sort by the leading punctuation, so that such entries collect in a group of their own.
+    #[default]
+    Group,
+    /// Ignore the leading punctuation, so that such entries file under their first letter.
+    Letter,
+}
+
+/// Rules for deriving the sort key of an entry from its text.
+#[derive(Clone, Debug)]
+pub(crate) struct SortKeys {
+    /// Characters (typically Markdown formatting) that are ignored.
+    pub ignore_chars: Vec<char>,
+    /// Leading words (e.g. articles) that are ignored, in lowercase.
+    pub ignore_words: Vec<String>,
+    /// Treatment of entries that start with punctuation.
+    pub symbols: Symbols,
+}
+
+impl Default for SortKeys {
+    fn default() -> Self {
+        Self {
+            ignore_chars: DEFAULT_IGNORE_CHARS.chars().collect(),
+            ignore_words: Vec::new(),
+            symbols: Symbols::default(),
+        }
+    }
+}
+
+impl SortKeys {
+    /// Return the sort key for an entry.
+    pub fn key(&self, entry: &str) -> String {
+        let mut key: String = entry
+            .chars()
+            .filter(|c| !self.ignore_chars.contains(c))
+            .collect();
+        if let Some((word, rest)) = key.split_once(char::is_whitespace) {
+            let rest = rest.trim_start();
+            if !rest.is_empty() && self.ignore_words.contains(&word.to_lowercase()) {
+                key = rest.to_string();
+            }
+        }
+        if self.symbols == Symbols::Letter {
+            let rest = key.trim_start_matches(|c: char| !c.is_alphanumeric());
+            if !rest.is_empty() {
+                key = rest.to_string();
+            }
+        }
+        key
+    }
+}
+
+/// Compare two strings by codepoint, except that runs of ASCII digits are compared by numeric value.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a = a.chars().peekable();
+    let mut b = b.chars().peekable();
+    loop {
+        match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x = digit_run(&mut a);
+                let y = digit_run(&mut b);
+                // Without leading zeros, a longer run of digits is a larger number.
+                let ordering = x
+                    .trim_start_matches('0')
+                    .len()
+                    .cmp(&y.trim_start_matches('0').len())
+                    .then_with(|| x.trim_start_matches('0').cmp(y.trim_start_matches('0')))
+                    .then_with(|| x.len().cmp(&y.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.cmp(y);
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Consume a run of ASCII digits from the front of `chars`.
+fn digit_run(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut run = String::new();
+    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+        run.push(c);
+    }
+    run
+}
+
+/// Split markup content into the text and its reading, if it has one (e.g. `所有権@しょゆうけん`).
+pub fn split_reading(content: &str) -> (&str, Option<&str>) {
+    match content.rsplit_once(READING_SEPARATOR) {
+        Some((text, reading))
+            if !text.is_empty() && !reading.is_empty() && reading.chars().all(is_kana) =>
+        {
+            (text, Some(reading))
+        }
+        _ => (content, None),
+    }
+}
+
+/// Indicate whether a character is hiragana or katakana.
+fn is_kana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{3096}' | '\u{30A1}'..='\u{30FA}' | 'ー' | '・')
+}
+
+/// Convert katakana to the equivalent hiragana, so that readings in either script sort together.
+pub(crate) fn to_hiragana(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// Return the label for the row of the Japanese syllabary that a character belongs to, if any.
+pub(crate) fn kana_row(c: char) -> Option<&'static str> {
+    let c = to_hiragana(&c.to_string());
+    KANA_ROWS
+        .iter()
+        .find(|(_, kana)| kana.contains(c.as_str()))
+        .map(|(label, _)| *label)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(collation: &Collation, words: &[&str]) -> Vec<String> {
+        let mut words: Vec<String> = words.iter().map(|s| s.to_string()).collect();
+        words.sort();
+        words.sort_by(|a, b| collation.compare(a, b));
+        words
+    }
+
+    #[test]
+    fn test_collation() {
+        let words = ["Zebra", "Äpfel", "apple", "Apple", "été", "zoo", "ñu", "nz"];
+        let cases = [
+            (
+                CODEPOINT,
+                vec!["Apple", "apple", "nz", "Zebra", "zoo", "Äpfel", "été", "ñu"],
+            ),
+            (
+                "en",
+                vec!["Äpfel", "Apple", "apple", "été", "ñu", "nz", "Zebra", "zoo"],
+            ),
+            (
+                "sv",
+                vec!["Apple", "apple", "été", "ñu", "nz", "Zebra", "zoo", "Äpfel"],
+            ),
+            (
+                "es",
+                vec!["Äpfel", "Apple", "apple", "été", "nz", "ñu", "Zebra", "zoo"],
+            ),
+        ];
+        for (locale, want) in cases {
+            let got = sorted(&Collation::new(locale, false), &words);
+            assert_eq!(got, want, "Mismatch for locale: {}", locale);
+        }
+    }
+
+    #[test]
+    fn test_natural() {
+        let words = [
+            "u8", "u128", "HTTP/11", "u16", "HTTP/2", "v007", "v7", "v10",
+        ];
+        let cases = [
+            (
+                CODEPOINT,
+                false,
+                vec![
+                    "HTTP/11", "HTTP/2", "u128", "u16", "u8", "v007", "v10", "v7",
+                ],
+            ),
+            (
+                CODEPOINT,
+                true,
+                vec![
+                    "HTTP/2", "HTTP/11", "u8", "u16", "u128", "v7", "v007", "v10",
+                ],
+            ),
+            (
+                "en",
+                true,
+                vec![
+                    "HTTP/2", "HTTP/11", "u8", "u16", "u128", "v007", "v7", "v10",
+                ],
+            ),
+        ];
+        for (locale, natural, want) in cases {
+            let got = sorted(&Collation::new(locale, natural), &words);
+            assert_eq!(
+                got, want,
+                "Mismatch for locale {}, natural {}",
+                locale, natural
+            );
+        }
+    }
+
+    #[test]
+    fn test_sort_key() {
+        let articles = SortKeys {
+            ignore_words: vec!["the".to_string(), "a".to_string()],
+            ..Default::default()
+        };
+        let letter = SortKeys {
+            symbols: Symbols::Letter,
+            ..Default::default()
+        };
+        let custom = SortKeys {
+            ignore_chars: vec!['`'],
+            ..Default::default()
+        };
+        let cases = [
+            (SortKeys::default(), "*italic*", "italic"),
+            (SortKeys::default(), "`&mut`", "&mut"),
+            (SortKeys::default(), "The Book", "The Book"),
+            (articles.clone(), "The Book", "Book"),
+            (articles.clone(), "a  priori", "priori"),
+            (articles.clone(), "Theory", "Theory"),
+            (articles, "The", "The"),
+            (letter.clone(), "`&mut`", "mut"),
+            (letter.clone(), "`#[cfg]`", "cfg"),
+            (letter, "`&&`", "&&"),
+            (custom, "*italic* `x`", "*italic* x"),
+        ];
+        for (keys, input, want) in cases {
+            let got = keys.key(input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_same_group() {
+        let cases = [
+            (CODEPOINT, "A", "a", true),
+            (CODEPOINT, "A", "Ä", false),
+            ("de", "A", "Ä", true),
+            ("sv", "A", "Ä", false),
+            ("en", "B", "A", false),
+        ];
+        for (locale, a, b, want) in cases {
+            let got = Collation::new(locale, false).same_group(a, b);
+            assert_eq!(got, want, "Mismatch for locale {}: {} vs {}", locale, a, b);
+        }
+    }
+
+    #[test]
+    fn test_split_reading() {
+        let cases = [
+            ("所有権@しょゆうけん", ("所有権", Some("しょゆうけん"))),
+            ("所有権@ショユウケン", ("所有権", Some("ショユウケン"))),
+            ("所有権", ("所有権", None)),
+            ("user@example.com", ("user@example.com", None)),
+            ("`@`", ("`@`", None)),
+            ("@しょ", ("@しょ", None)),
+        ];
+        for (input, want) in cases {
+            let got = split_reading(input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_kana_row() {
+        let cases = [
+            ('あ', Some("あ行")),
+            ('し', Some("さ行")),
+            ('ジ', Some("さ行")),
+            ('ぱ', Some("は行")),
+            ('ン', Some("わ行")),
+            ('a', None),
+            ('所', None),
+        ];
+        for (input, want) in cases {
+            let got = kana_row(input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_invalid_locale() {
+        assert!(matches!(
+            Collation::new("not a locale", false),
+            Collation::Codepoint
+        ));
+    }
+}
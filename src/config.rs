@@ -0,0 +1,211 @@
+//! Configuration of the preprocessor, from the `[preprocessor.indexing]` section of `book.toml`.
+
+use crate::{collation::Symbols, Backlinks, ByPart, Drafts};
+use mdbook_preprocessor::{errors::Error, PreprocessorContext};
+use serde::{de::Error as _, Deserialize, Deserializer};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+};
+
+/// Name of the section of the book configuration that holds the preprocessor's configuration.
+const SECTION: &str = "preprocessor.indexing";
+
+/// Keys of the preprocessor's configuration section that are interpreted by mdbook itself.
+const MDBOOK_KEYS: &[&str] = &["command", "renderers", "before", "after", "optional"];
+
+/// A configuration value that can be given either as a single string or as an array of strings.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq)]
+#[serde(untagged)]
+pub(crate) enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    /// Return the values, splitting a single string on `separator` (if given).
+    pub fn into_vec(self, separator: Option<char>) -> Vec<String> {
+        match (self, separator) {
+            (Self::One(value), Some(separator)) => value
+                .split(separator)
+                .map(|s| s.trim().to_string())
+                .collect(),
+            (Self::One(value), None) => vec![value],
+            (Self::Many(values), _) => values,
+        }
+    }
+}
+
+/// Typed form of the preprocessor's configuration section.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub(crate) struct Config {
+    /// Renderers for which no index is generated, as a comma-separated string or an array.
+    pub skip_renderer: Option<OneOrMany>,
+    /// Index backend to use for each renderer.
+    #[serde(deserialize_with = "backend_names")]
+    pub backend: BTreeMap<String, String>,
+    pub see_instead: BTreeMap<String, String>,
+    pub see_also: BTreeMap<String, OneOrMany>,
+    pub nest_under: BTreeMap<String, String>,
+    pub exclude_chapters: Vec<String>,
+    pub drafts: Drafts,
+    pub use_chapter_names: bool,
+    pub backlinks: Backlinks,
+    pub occurrence_nav: bool,
+    pub search_terms: Option<PathBuf>,
+    pub filter: bool,
+    pub previews: bool,
+    pub locator_ranges: bool,
+    pub suppress_head: bool,
+    pub strings: BTreeMap<String, String>,
+    pub natural_sort: bool,
+    pub collation: Option<String>,
+    pub ignore_chars: Option<String>,
+    pub ignore_words: Vec<String>,
+    pub symbols: Symbols,
+    pub group_headings: bool,
+    pub by_part: ByPart,
+    pub readings: HashMap<String, String>,
+    pub glossary: HashMap<String, String>,
+    pub index_file: Option<PathBuf>,
+    /// Keys that are not recognized (by this preprocessor, at least).
+    #[serde(flatten)]
+    other: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    /// Load the configuration from `ctx`, failing if any of the recognized keys has a value of the wrong type.
+    pub fn load(ctx: &PreprocessorContext) -> Result<Self, Error> {
+        let config: Self = ctx.config.get(SECTION)?.unwrap_or_default();
+        for key in config.unknown_keys() {
+            log::warn!("Ignoring unknown configuration key '{SECTION}.{key}'");
+        }
+        Ok(config)
+    }
+
+    /// Parse the configuration from the TOML contents of the preprocessor's section.
+    #[cfg(test)]
+    fn parse(text: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(text)
+    }
+
+    /// Return the keys that are recognized by neither this preprocessor nor mdbook.
+    pub fn unknown_keys(&self) -> impl Iterator<Item = &str> {
+        self.other
+            .keys()
+            .map(String::as_str)
+            .filter(|key| !MDBOOK_KEYS.contains(key))
+    }
+}
+
+/// Deserialize the map from renderer to index backend, failing for a backend that doesn't exist.
+fn backend_names<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, String>, D::Error> {
+    let backends = BTreeMap::<String, String>::deserialize(deserializer)?;
+    for (renderer, name) in &backends {
+        if crate::backend::by_name(name).is_none() {
+            return Err(D::Error::custom(format!(
+                "unknown index backend '{name}' for renderer '{renderer}'"
+            )));
+        }
+    }
+    Ok(backends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skip_renderer() {
+        let cases = [
+            (r#"skip_renderer = "latex""#, vec!["latex"]),
+            (r#"skip_renderer = "latex, epub""#, vec!["latex", "epub"]),
+            (
+                r#"skip_renderer = ["latex", "epub"]"#,
+                vec!["latex", "epub"],
+            ),
+            ("", vec![]),
+        ];
+        for (input, want) in cases {
+            let got = Config::parse(input)
+                .unwrap()
+                .skip_renderer
+                .map(|v| v.into_vec(Some(',')))
+                .unwrap_or_default();
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let config = Config::parse(
+            r#"
+command = "mdbook-indexing"
+use_chapter_names = true
+locator_ranges = true
+backlinks = "marker"
+by_part = "sections"
+symbols = "letter"
+ignore_words = ["the"]
+drafts = "warn"
+
+[backend]
+"my-html-renderer" = "html"
+
+[see_also]
+"a" = "b"
+"c" = ["d", "e"]
+"#,
+        )
+        .unwrap();
+        assert!(config.use_chapter_names);
+        assert!(config.locator_ranges);
+        assert_eq!(config.backlinks, Backlinks::Marker);
+        assert_eq!(config.by_part, ByPart::Sections);
+        assert_eq!(config.symbols, Symbols::Letter);
+        assert_eq!(config.ignore_words, vec!["the"]);
+        assert_eq!(config.drafts, Drafts::Warn);
+        assert_eq!(config.backend["my-html-renderer"], "html");
+        assert_eq!(config.see_also["a"], OneOrMany::One("b".to_string()));
+        assert_eq!(
+            config.see_also["c"],
+            OneOrMany::Many(vec!["d".to_string(), "e".to_string()])
+        );
+        assert_eq!(config.unknown_keys().count(), 0);
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let config = Config::parse("suppress_heads = true\nrenderers = [\"html\"]").unwrap();
+        assert!(!config.suppress_head);
+        assert_eq!(
+            config.unknown_keys().collect::<Vec<_>>(),
+            vec!["suppress_heads"]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            r#"use_chapter_names = "true""#,
+            r#"backlinks = "links""#,
+            r#"by_part = "chapters""#,
+            r#"symbols = "none""#,
+            r#"drafts = "skip""#,
+            "skip_renderer = 1",
+            "ignore_words = \"the\"",
+            "[readings]\nx = 1",
+            "[backend]\nhtml = \"htlm\"",
+        ];
+        for input in cases {
+            assert!(
+                Config::parse(input).is_err(),
+                "Unexpected success for input: {}",
+                input
+            );
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Per-chapter index metadata, in a TOML front-matter block delimited by `+++` lines at the top of a chapter.
+
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Line that starts and ends a front-matter block.
+const DELIMITER: &str = "+++";
+
+/// Index metadata for a chapter.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub(crate) struct FrontMatter {
+    /// Hidden index entries for the chapter as a whole, located at the top of the chapter.
+    pub index: Vec<String>,
+    /// Index entries whose markup in the chapter does not generate a location.
+    pub exclude: Vec<String>,
+    /// Keys that are not recognized.
+    #[serde(flatten)]
+    other: BTreeMap<String, toml::Value>,
+}
+
+impl FrontMatter {
+    /// Return the keys that are not recognized.
+    pub fn unknown_keys(&self) -> impl Iterator<Item = &str> {
+        self.other.keys().map(String::as_str)
+    }
+}
+
+/// Split the front matter (if any) from the start of a chapter, returning it along with the rest of the chapter.
+///
+/// A block that is valid TOML is always split off, even when its values are not valid front matter, so that it never
+/// appears in the output.  A block that is not valid TOML is left in place.
+pub(crate) fn split(content: &str) -> (Result<FrontMatter, toml::de::Error>, &str) {
+    match delimited(content) {
+        Some((block, rest)) => match toml::from_str::<toml::Table>(block) {
+            Ok(table) => (toml::Value::Table(table).try_into(), rest),
+            Err(e) => (Err(e), content),
+        },
+        None => (Ok(FrontMatter::default()), content),
+    }
+}
+
+/// Find a front-matter block at the start of `content`, returning its contents and the text that follows it.
+fn delimited(content: &str) -> Option<(&str, &str)> {
+    let rest = content.strip_prefix(DELIMITER)?;
+    let rest = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))?;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == DELIMITER {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        offset += line.len();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split() {
+        let unsafe_entry = FrontMatter {
+            index: vec!["unsafe".to_string()],
+            ..Default::default()
+        };
+        let cases = [
+            ("# Title\n", FrontMatter::default(), "# Title\n"),
+            (
+                "+++\nindex = [\"unsafe\"]\n+++\n# Title\n",
+                unsafe_entry.clone(),
+                "# Title\n",
+            ),
+            (
+                "+++\r\nindex = [\"unsafe\"]\r\n+++\r\n# Title\r\n",
+                unsafe_entry,
+                "# Title\r\n",
+            ),
+            (
+                "+++\nexclude = [\"a\", \"b\"]\n+++",
+                FrontMatter {
+                    exclude: vec!["a".to_string(), "b".to_string()],
+                    ..Default::default()
+                },
+                "",
+            ),
+            ("+++\n+++\ntext", FrontMatter::default(), "text"),
+            // Not front matter, as the block is not terminated or does not start on the first line.
+            (
+                "+++\nindex = []\n",
+                FrontMatter::default(),
+                "+++\nindex = []\n",
+            ),
+            (
+                "\n+++\nindex = []\n+++\n",
+                FrontMatter::default(),
+                "\n+++\nindex = []\n+++\n",
+            ),
+            ("+++ text", FrontMatter::default(), "+++ text"),
+            // Unknown keys are kept aside, to be reported.
+            (
+                "+++\nindexes = [\"unsafe\"]\n+++\ntext",
+                FrontMatter {
+                    other: BTreeMap::from([(
+                        "indexes".to_string(),
+                        toml::Value::Array(vec!["unsafe".into()]),
+                    )]),
+                    ..Default::default()
+                },
+                "text",
+            ),
+        ];
+        for (input, want_front_matter, want_rest) in cases {
+            let (got_front_matter, got_rest) = split(input);
+            let got_front_matter = got_front_matter.unwrap();
+            assert_eq!(
+                got_front_matter, want_front_matter,
+                "Mismatch for input: {}",
+                input
+            );
+            assert_eq!(got_rest, want_rest, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_split_errors() {
+        let cases = [
+            // Valid TOML is split off regardless.
+            ("+++\nindex = \"unsafe\"\n+++\ntext", "text"),
+            ("+++\nindex = [\n+++\ntext", "+++\nindex = [\n+++\ntext"),
+        ];
+        for (input, want_rest) in cases {
+            let (got_front_matter, got_rest) = split(input);
+            assert!(
+                got_front_matter.is_err(),
+                "Unexpected success for input: {}",
+                input
+            );
+            assert_eq!(got_rest, want_rest, "Mismatch for input: {}", input);
+        }
+    }
+}
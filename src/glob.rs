@@ -0,0 +1,59 @@
+//! Matching of chapter paths against glob patterns.
+
+/// Indicate whether `path` (with `/` separators) matches `pattern`.
+///
+/// In the pattern, `?` matches any character other than `/`, `*` matches any run of characters other than `/`, and
+/// `**` matches any run of characters (so `**/` matches any number of leading directories).
+pub(crate) fn matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let path: Vec<char> = path.chars().collect();
+    matches_from(&pattern, &path)
+}
+
+fn matches_from(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            matches_from(rest, path)
+                || (0..path.len()).any(|i| path[i] == '/' && matches_from(rest, &path[i + 1..]))
+        }
+        ['*', '*', rest @ ..] => (0..=path.len()).any(|i| matches_from(rest, &path[i..])),
+        ['*', rest @ ..] => (0..=path.len())
+            .take_while(|&i| i == 0 || path[i - 1] != '/')
+            .any(|i| matches_from(rest, &path[i..])),
+        ['?', rest @ ..] => {
+            matches!(path.first(), Some(c) if *c != '/') && matches_from(rest, &path[1..])
+        }
+        [c, rest @ ..] => path.first() == Some(c) && matches_from(rest, &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let cases = [
+            ("CHANGELOG.md", "CHANGELOG.md", true),
+            ("CHANGELOG.md", "docs/CHANGELOG.md", false),
+            ("*.md", "intro.md", true),
+            ("*.md", "api/intro.md", false),
+            ("api/*", "api/intro.md", true),
+            ("api/*", "api/sub/intro.md", false),
+            ("api/**", "api/sub/intro.md", true),
+            ("**/CHANGELOG.md", "CHANGELOG.md", true),
+            ("**/CHANGELOG.md", "a/b/CHANGELOG.md", true),
+            ("**/CHANGELOG.md", "a/bCHANGELOG.md", false),
+            ("ch?.md", "ch1.md", true),
+            ("ch?.md", "ch10.md", false),
+            ("ch?/x.md", "ch/x.md", false),
+            ("", "", true),
+            ("", "x", false),
+        ];
+        for (pattern, path, want) in cases {
+            let got = matches(pattern, path);
+            assert_eq!(got, want, "Mismatch for input: {} {}", pattern, path);
+        }
+    }
+}
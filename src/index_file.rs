@@ -0,0 +1,103 @@
+//! Index configuration file, which describes index entries in more detail than is possible in `book.toml`.
+
+use mdbook_preprocessor::errors::Error;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::Path};
+
+/// Description of an index entry, keyed by the (canonicalized) entry in the index configuration file.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub(crate) struct EntryConfig {
+    /// Form of the entry to display in the index, if different from the entry.
+    pub display: Option<String>,
+    /// Key that determines the sort order of the entry, overriding any reading of the entry.
+    pub sort: Option<String>,
+    /// Entry that this entry is nested under.
+    pub parent: Option<String>,
+    /// Other index text that is accumulated against this entry.
+    pub aliases: Vec<String>,
+    /// Entry that this entry redirects to.
+    pub see: Option<String>,
+    /// Related entries that this entry also refers to.
+    pub see_also: Vec<String>,
+    /// Description of the entry, which is shown in the index (and used as its definition in the glossary).
+    pub description: Option<String>,
+    /// External link for the entry.
+    pub link: Option<String>,
+}
+
+/// Load an index configuration file.
+pub(crate) fn load(path: &Path) -> Result<BTreeMap<String, EntryConfig>, Error> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| Error::msg(format!("Failed to read {}: {e}", path.display())))?;
+    parse(&contents).map_err(|e| Error::msg(format!("Failed to parse {}: {e}", path.display())))
+}
+
+/// Parse the contents of an index configuration file.
+fn parse(contents: &str) -> Result<BTreeMap<String, EntryConfig>, toml::de::Error> {
+    toml::from_str(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let contents = r#"
+["borrow checker"]
+display = "borrow checker, the"
+sort = "borrowck"
+parent = "borrowing"
+aliases = ["borrowck"]
+see_also = ["lifetimes"]
+description = "Checks references."
+link = "https://example.com/borrowck"
+
+[unsafe]
+see = "`unsafe`"
+"#;
+        let got = parse(contents).unwrap();
+        let want = BTreeMap::from([
+            (
+                "borrow checker".to_string(),
+                EntryConfig {
+                    display: Some("borrow checker, the".to_string()),
+                    sort: Some("borrowck".to_string()),
+                    parent: Some("borrowing".to_string()),
+                    aliases: vec!["borrowck".to_string()],
+                    see: None,
+                    see_also: vec!["lifetimes".to_string()],
+                    description: Some("Checks references.".to_string()),
+                    link: Some("https://example.com/borrowck".to_string()),
+                },
+            ),
+            (
+                "unsafe".to_string(),
+                EntryConfig {
+                    see: Some("`unsafe`".to_string()),
+                    ..Default::default()
+                },
+            ),
+        ]);
+        assert_eq!(got, want);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("[a]\nparent = \"b\"\nsee_also = \"c\"\n", "line 3"),
+            ("[a]\n\ndisplya = \"A\"\n", "line 3"),
+            ("[a]\nsort = \n", "line 2"),
+        ];
+        for (input, want) in cases {
+            let got = parse(input).unwrap_err().to_string();
+            assert!(
+                got.contains(want),
+                "Mismatch for input {:?}: {}",
+                input,
+                got
+            );
+        }
+    }
+}
@@ -0,0 +1,1809 @@
+//! mdbook preprocessor that assembles an index
+//!
+//! Phrases enclosed in `{{i:<text>}}` are transmitted as-is to the rendered output, but also get an index entry added for them.
+//!
+//! Phrases enclosed in `{{hi:<text>}}` are removed from the rendered output, but get an index entry added for them anyway.
+//!
+//! A book chapter with title "Index" will have its contents replaced by the accumulated index.
+//!
+//! Phrases enclosed in `{{g:<text>}}` are treated like `{{i:<text>}}`, but are also listed in the glossary, and a book
+//! chapter with title "Glossary" will have its contents replaced by the glossary.  Definitions of glossary terms come
+//! from the `[preprocessor.indexing.glossary]` section of the `book.toml` configuration file.
+//!
+//! A `{{index:chapter}}` directive is replaced by a compact index of the entries in the current chapter, and a
+//! `{{index:part}}` directive by a compact index of the entries in the current part of the book.
+//!
+//! A chapter can start with a TOML front-matter block, delimited by `+++` lines, that lists hidden index entries for the
+//! chapter as a whole (`index`) and entries whose markup in the chapter is not indexed (`exclude`).
+//!
+//! Key-value pairs in the `[preprocessor.indexing.see_instead]` section of the `book.toml` configuration file indicate index
+//! entries where the key should point to the value.  Thus an entry like:
+//!
+//! ```toml
+//! "unit type" = "`()`"
+//! ```
+//!
+//! would result in an index entry that says: "unit type, see `()`" (instead of a list of locations).
+//!
+//! Key-value pairs in the `[preprocessor.indexing.nest_under]` section of the `book.toml` configuration file indicate index
+//! entries where the entry for the key should be nested under value.  Thus an entry like:
+//!
+//! ```toml
+//! "generic type" = "generics"
+//! ```
+//!
+//! would result in the index entry for "generic type" being only listed as an indented sub-entry under "generics".
+//!
+//! Tips on usage:
+//!
+//! - Avoid putting the index inside a link, as it breaks the link, i.e. prefer:
+//!     ```md
+//!     {{i:[text](http:link)}}
+//!     ```
+//!   to:
+//!     ```md
+//!     [{{i:text}}](http:link)
+//!     ```
+//!
+
+use backend::{IndexBackend, Marker};
+pub use collation::split_reading;
+use collation::{kana_row, to_hiragana, Collation, SortKeys};
+use config::Config;
+use front_matter::FrontMatter;
+use mdbook_preprocessor::{
+    book::{Book, BookItem},
+    errors::Error,
+    Preprocessor, PreprocessorContext, MDBOOK_VERSION,
+};
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+    borrow::Borrow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+    sync::LazyLock,
+};
+use strings::Strings;
+
+pub mod backend;
+mod collation;
+mod config;
+mod front_matter;
+mod glob;
+mod index_file;
+mod search;
+mod strings;
+
+/// Name of the preprocessor, as used in the `[preprocessor.indexing]` section of `book.toml`.
+pub const NAME: &str = "indexing";
+
+/// Command for a visible index entry.
+const VISIBLE: &str = "i";
+/// Command for a hidden index entry.
+const HIDDEN: &str = "hi";
+/// Command for a visible index entry, italicized.
+const ITALIC: &str = "ii";
+/// Command for a visible index entry that is also a glossary term.
+const GLOSSARY: &str = "g";
+
+/// Maximum number of characters of context on each side of index markup in a preview snippet.
+const SNIPPET_CONTEXT: usize = 40;
+
+/// Maximum number of levels of nesting for index entries.
+const MAX_LEVELS: usize = 3;
+
+/// Escape character.
+const ESCAPE_CHAR: char = '\\';
+
+/// Regular expression to match indexing commands.
+static INDEX_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?x)             # insignificant whitespace mode
+              (?s)             # dot matches newline
+              \\\{\{[^}]*\}\}  # match escaped link
+              |                # or
+              \{\{             # opening braces
+              (?P<viz>[hi]?i|g) # visibility command (i, hi, ii, g)
+              :                # separator
+              \s*              # ignore leading whitespace
+              (?P<content>.*?) # index entry
+              \}\}             # closing braces",
+    )
+    .unwrap()
+});
+
+/// Scope of a directive that generates a compact index of the current chapter.
+const CHAPTER_SCOPE: &str = "chapter";
+/// Scope of a directive that generates a compact index of the current part of the book.
+const PART_SCOPE: &str = "part";
+
+/// Regular expression to match (possibly escaped) directives for a compact index of part of the book.
+static DIRECTIVE_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?P<escape>\\)?\{\{index:\s*(?P<scope>chapter|part)\s*\}\}").unwrap()
+});
+
+/// Regular expression to match a placeholder for navigation between the occurrences of an entry.
+static NAV_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<!--index-nav:(?P<anchor>a\d+)-->").unwrap());
+
+/// Regular expression to match a placeholder for the identifier of the entry that a backlink points to.
+static ID_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<!--index-id:(?P<n>\d+)-->").unwrap());
+
+/// Styling that only shows navigation between occurrences of an entry for the occurrence that the reader has
+/// navigated to.
+const NAV_STYLE: &str =
+    "<style>.index-nav { display: none; } a:target + .index-nav { display: inline; }</style>\n\n";
+
+/// Regular expression to match a Markdown link.
+static MD_LINK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)\[(?P<text>[^]]+)\]\((?P<link>[^)]+)\)").unwrap());
+/// Regular expression for whitespace.
+static WHITESPACE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)\s+").unwrap());
+
+/// Sorted sub-entries for each index entry that has any, keyed by the chain of entries from the top level down to the
+/// entry.
+type SubEntries = HashMap<Vec<String>, Vec<String>>;
+
+/// A group of index entries, with an optional label.
+type Group = (Option<String>, Vec<String>);
+
+/// The part of the book that a section of the generated index covers, when the index is split into a section for each
+/// part: `Some(None)` covers the chapters that are not in any part, and `None` means the index is not split.
+type Section<'a> = Option<Option<&'a str>>;
+
+/// Location of an index anchor in the source book.
+#[derive(Clone, Debug)]
+pub struct Location {
+    /// File in source book.
+    pub path: Option<PathBuf>,
+    /// Title of the part of the book that contains the chapter, if any.
+    pub part: Option<String>,
+    /// Chapter name in source book.
+    pub name: String,
+    /// Anchor identifier.
+    pub anchor: String,
+    /// Plain text around the index markup, for previewing the location.
+    pub snippet: String,
+}
+
+/// How the parts of the book (as delimited by part titles in the summary) are reflected in the generated index.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ByPart {
+    /// Parts are not reflected in the index.
+    #[default]
+    Ignore,
+    /// The locators for each entry are grouped by part.
+    Locators,
+    /// The index has a separate section for each part.
+    Sections,
+}
+
+/// How occurrences of index markup link back to their entry in the generated index.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Backlinks {
+    /// Occurrences don't link to the index.
+    #[default]
+    None,
+    /// The text of visible index markup links to the index.
+    Link,
+    /// A small marker after the index markup links to the index.
+    Marker,
+}
+
+/// How draft chapters (which have no source file, and so no location to link to) are indexed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Drafts {
+    /// Draft chapters are not indexed.
+    Exclude,
+    /// Draft chapters are indexed with unlinked locators, with a warning.
+    Warn,
+    /// Draft chapters are indexed with unlinked locators.
+    #[default]
+    Include,
+}
+
+/// A pre-processor that tracks index entries.
+#[derive(Default)]
+pub struct Index {
+    /// Renderers for which no indexing content should be emitted.
+    skip_renderer: HashSet<String>,
+    /// Output backend names to use for specific renderers.
+    backends: HashMap<String, String>,
+    /// Index entries that redirect to a different entry.
+    see_instead: HashMap<String, String>,
+    /// Index entries that should also refer to other related entries.
+    see_also: HashMap<String, Vec<String>>,
+    /// Index entries that should appear in the index as sub-entries underneath the specified top-level entry.
+    nest_under: HashMap<String, String>,
+    /// Index text that is accumulated against a different entry, without a reference from the text.
+    aliases: HashMap<String, String>,
+    /// Forms of index entries to display in the index, where different from the entry.
+    displays: HashMap<String, String>,
+    /// External links for index entries.
+    links: HashMap<String, String>,
+    /// How occurrences of index markup link back to the generated index.
+    backlinks: Backlinks,
+    /// Names of chapters, or glob patterns for the paths of chapters, that are not indexed.
+    exclude_chapters: Vec<String>,
+    /// How draft chapters are indexed.
+    drafts: Drafts,
+    /// Whether to skip a "head, " prefix in sub-entries where the prefix matches the top-level entry.
+    suppress_head: bool,
+    /// Emit chapter names as the link text in the generated index.
+    use_chapter_names: bool,
+    /// Include navigation between the occurrences of an entry at each occurrence.
+    occurrence_nav: bool,
+    /// File, relative to the root directory of the book, to write index entries to for use by the HTML search.
+    search_terms: Option<PathBuf>,
+    /// Include a box for filtering the entries of the generated index.
+    filter: bool,
+    /// Include a preview of the text around each location in the generated index.
+    previews: bool,
+    /// Show runs of locations in the same chapter as a range of locators.
+    locator_ranges: bool,
+    /// Text to use in the generated index.
+    strings: Strings,
+    /// Method of ordering entries in the generated index.
+    collation: Collation,
+    /// Rules for deriving the sort key of an entry.
+    sort_keys: SortKeys,
+    /// Whether to split the generated index into groups with a heading for each initial letter.
+    group_headings: bool,
+    /// How parts of the book are reflected in the generated index.
+    by_part: ByPart,
+    /// Readings of index entries, which determine their sort order.
+    readings: RefCell<HashMap<String, String>>,
+    /// Explicit sort keys of index entries, which take precedence over their readings.
+    sort_as: HashMap<String, String>,
+    /// Descriptions of index entries, which are shown in the generated index.
+    descriptions: HashMap<String, String>,
+    /// Definitions of glossary terms.
+    glossary: HashMap<String, String>,
+    /// Glossary terms marked in the text of the book.
+    glossary_terms: RefCell<HashSet<String>>,
+    /// Location of the index chapter in the source book.
+    index_path: RefCell<Option<PathBuf>>,
+    /// Location of the glossary chapter in the source book.
+    glossary_path: RefCell<Option<PathBuf>>,
+    /// Titles of the parts of the book, in order.
+    parts: RefCell<Vec<String>>,
+    /// List of index anchor locations for each (canonicalized) index entry.
+    entries: RefCell<HashMap<String, Vec<Location>>>,
+    /// Entries that backlinks point to, indexed by the number in their placeholder.
+    backlink_targets: RefCell<Vec<String>>,
+    /// Unique identifier for each entry, computed when first needed after all entries are known.
+    entry_ids: RefCell<HashMap<String, String>>,
+}
+
+/// Indicate whether `content` has any (unescaped) glossary markup.
+fn has_glossary_markup(content: &str) -> bool {
+    INDEX_RE
+        .captures_iter(content)
+        .any(|caps| caps.name("viz").is_some_and(|viz| viz.as_str() == GLOSSARY))
+}
+
+/// Return a placeholder for navigation between the occurrences of an entry, for the occurrence at `anchor`.
+fn nav_placeholder(anchor: &str) -> String {
+    format!("<!--index-nav:{anchor}-->")
+}
+
+/// Return a stable identifier for an entry, for use as an anchor in a generated page.
+fn entry_id(prefix: &str, entry: &str) -> String {
+    let mut id = prefix.to_string();
+    for c in entry.chars() {
+        if c.is_alphanumeric() {
+            id.push(c);
+        } else if !id.ends_with('-') {
+            id.push('-');
+        }
+    }
+    id.trim_end_matches('-').to_string()
+}
+
+/// Split a list of locations into runs of locations in the same part of the book.
+fn group_by_part(locations: &[Location]) -> Vec<(Option<&str>, Vec<&Location>)> {
+    let mut groups: Vec<(Option<&str>, Vec<&Location>)> = Vec::new();
+    for loc in locations {
+        match groups.last_mut() {
+            Some((part, locs)) if *part == loc.part.as_deref() => locs.push(loc),
+            _ => groups.push((loc.part.as_deref(), vec![loc])),
+        }
+    }
+    groups
+}
+
+/// Split a list of locations into runs that are each shown as a single locator or as a range, returning the positions
+/// of the first and last location of each run.  A range covers three or more consecutive locations in the same chapter.
+fn locator_runs<L: Borrow<Location>>(locations: &[L]) -> Vec<(usize, usize)> {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for (idx, loc) in locations.iter().enumerate() {
+        let path = &loc.borrow().path;
+        match runs.last_mut() {
+            Some((_, last)) if path.is_some() && locations[*last].borrow().path == *path => {
+                *last = idx
+            }
+            _ => runs.push((idx, idx)),
+        }
+    }
+    // A range of two locations is no shorter than listing them both.
+    runs.into_iter()
+        .flat_map(|(first, last)| match last - first {
+            0 | 1 => (first..=last).map(|idx| (idx, idx)).collect(),
+            _ => vec![(first, last)],
+        })
+        .collect()
+}
+
+/// Return a link from the chapter at `from` (or from the root of the book if `None`) to the file at `to`, which are
+/// both relative to the root of the book.
+fn relative_link(from: Option<&Path>, to: &Path) -> String {
+    let from_dir: Vec<Component> = from
+        .and_then(Path::parent)
+        .map(|dir| dir.components().collect())
+        .unwrap_or_default();
+    let to: Vec<Component> = to.components().collect();
+    let common = from_dir
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut parts = vec![".."; from_dir.len() - common];
+    parts.extend(to[common..].iter().filter_map(|c| c.as_os_str().to_str()));
+    parts.join("/")
+}
+
+/// Convert index text to a canonical form suitable for inclusion in the index.
+pub fn canonicalize(s: &str) -> String {
+    // Remove any links from the index name.
+    let delinked = MD_LINK_RE.replace_all(s, "$text").to_string();
+
+    // Canonicalize whitespace.
+    WHITESPACE_RE.replace_all(&delinked, " ").to_string()
+}
+
+/// Return a plain text snippet of the paragraph around the index markup at `start..end` in `content`.
+fn snippet(content: &str, start: usize, end: usize) -> String {
+    // Only include text from the same paragraph.
+    let before = &content[..start];
+    let before = before.rsplit_once("\n\n").map_or(before, |(_, text)| text);
+    let after = &content[end..];
+    let after = after.split_once("\n\n").map_or(after, |(text, _)| text);
+    let markup = plain_text(&content[start..end]);
+    let before = plain_text(before);
+    let after = plain_text(after);
+
+    // Truncate the context at word boundaries, or at any character for text (e.g. Japanese) without spaces.
+    let mut result = String::new();
+    let skip = before.chars().count().saturating_sub(SNIPPET_CONTEXT);
+    if skip > 0 {
+        let text: String = before.chars().skip(skip).collect();
+        result += "…";
+        result += text.split_once(' ').map_or(text.as_str(), |(_, text)| text);
+    } else {
+        result += &before;
+    }
+    result += &markup;
+    if after.chars().count() > SNIPPET_CONTEXT {
+        let text: String = after.chars().take(SNIPPET_CONTEXT).collect();
+        result += text
+            .rsplit_once(' ')
+            .map_or(text.as_str(), |(text, _)| text);
+        result += "…";
+    } else {
+        result += &after;
+    }
+    result.trim().to_string()
+}
+
+/// Convert Markdown text (including index markup) into plain text.
+fn plain_text(text: &str) -> String {
+    let text = INDEX_RE.replace_all(text, |caps: &regex::Captures| match caps.name("viz") {
+        Some(viz) if viz.as_str() == HIDDEN => String::new(),
+        Some(_) => split_reading(&caps["content"]).0.to_string(),
+        None => caps[0][1..].to_string(),
+    });
+    let text = MD_LINK_RE.replace_all(&text, "$text");
+    let text: String = text
+        .chars()
+        .filter(|c| !matches!(c, '*' | '_' | '`' | '#' | '<' | '>'))
+        .collect();
+    WHITESPACE_RE.replace_all(&text, " ").to_string()
+}
+
+impl Index {
+    /// Create a new preprocessor, based on configuration in `ctx`.
+    pub fn new(ctx: &PreprocessorContext) -> Result<Self, Error> {
+        if ctx.mdbook_version != MDBOOK_VERSION {
+            // We should probably use the `semver` crate to check compatibility here...
+            eprintln!(
+                "Warning: The {NAME} plugin was built against version {MDBOOK_VERSION} of mdbook, \
+                 but we're being called from version {}",
+                ctx.mdbook_version
+            );
+        }
+
+        let config = Config::load(ctx)?;
+
+        let mut skip_renderer = HashSet::new();
+        if let Some(val) = config.skip_renderer {
+            let val = val.into_vec(Some(','));
+            log::info!("Skipping output for renderers in: {}", val.join(","));
+            skip_renderer.extend(val);
+        }
+
+        let mut backends = HashMap::new();
+        for (key, value) in config.backend {
+            log::info!("Renderer '{}' will use the '{}' backend", key, value);
+            backends.insert(key, value);
+        }
+
+        let mut see_instead = HashMap::new();
+        for (key, value) in config.see_instead {
+            log::info!("Index entry '{}' will be 'see {}'", key, value);
+            see_instead.insert(key, value);
+        }
+
+        let mut see_also = HashMap::new();
+        for (key, values) in config.see_also {
+            let values = values.into_vec(None);
+            log::info!("Index entry '{}' will be 'see also {:?}'", key, values);
+            see_also.insert(key, values);
+        }
+
+        let mut nest_under = HashMap::new();
+        for (key, value) in config.nest_under {
+            log::info!("Index entry '{}' will be nested under '{}'", key, value);
+            nest_under.insert(key, value);
+        }
+
+        for pattern in &config.exclude_chapters {
+            log::info!("Chapters matching '{}' will not be indexed", pattern);
+        }
+
+        if let Some(val) = &config.search_terms {
+            log::info!(
+                "Index entries for search will be written to '{}'",
+                val.display()
+            );
+        }
+
+        let mut strings = Strings::for_language(ctx.config.book.language.as_deref());
+        for (key, value) in config.strings {
+            if strings.set(&key, &value) {
+                log::info!("Index text '{}' will be '{}'", key, value);
+            } else {
+                log::warn!("Ignoring unknown index text '{}'", key);
+            }
+        }
+
+        let collation = Collation::new(
+            config
+                .collation
+                .as_deref()
+                .or(ctx.config.book.language.as_deref())
+                .unwrap_or(collation::CODEPOINT),
+            config.natural_sort,
+        );
+
+        let mut sort_keys = SortKeys {
+            symbols: config.symbols,
+            ..Default::default()
+        };
+        if let Some(val) = config.ignore_chars {
+            log::info!("Ignoring characters '{}' when sorting", val);
+            sort_keys.ignore_chars = val.chars().collect();
+        }
+        for word in config.ignore_words {
+            log::info!("Ignoring leading word '{}' when sorting", word);
+            sort_keys.ignore_words.push(word.to_lowercase());
+        }
+
+        for (key, value) in &config.readings {
+            log::info!("Index entry '{}' will sort as '{}'", key, value);
+        }
+        for (key, value) in &config.glossary {
+            log::info!("Glossary term '{}' is defined as '{}'", key, value);
+        }
+
+        let mut index = Self {
+            skip_renderer,
+            backends,
+            see_instead,
+            see_also,
+            nest_under,
+            aliases: HashMap::new(),
+            displays: HashMap::new(),
+            links: HashMap::new(),
+            exclude_chapters: config.exclude_chapters,
+            drafts: config.drafts,
+            use_chapter_names: config.use_chapter_names,
+            backlinks: config.backlinks,
+            occurrence_nav: config.occurrence_nav,
+            search_terms: config.search_terms,
+            filter: config.filter,
+            previews: config.previews,
+            locator_ranges: config.locator_ranges,
+            suppress_head: config.suppress_head,
+            strings,
+            collation,
+            sort_keys,
+            group_headings: config.group_headings,
+            by_part: config.by_part,
+            glossary: config.glossary,
+            glossary_terms: RefCell::new(HashSet::new()),
+            index_path: RefCell::new(None),
+            glossary_path: RefCell::new(None),
+            parts: RefCell::new(Vec::new()),
+            readings: RefCell::new(config.readings),
+            sort_as: HashMap::new(),
+            descriptions: HashMap::new(),
+            entries: RefCell::new(HashMap::new()),
+            backlink_targets: RefCell::new(Vec::new()),
+            entry_ids: RefCell::new(HashMap::new()),
+        };
+        index.load_index_file(ctx, config.index_file)?;
+        index.check_nesting()?;
+        Ok(index)
+    }
+
+    /// Load the descriptions of index entries from the index configuration file, if there is one.
+    fn load_index_file(
+        &mut self,
+        ctx: &PreprocessorContext,
+        index_file: Option<PathBuf>,
+    ) -> Result<(), Error> {
+        let Some(path) = index_file.map(|val| ctx.root.join(val)) else {
+            return Ok(());
+        };
+        log::info!("Loading index entries from {}", path.display());
+        for (entry, config) in index_file::load(&path)? {
+            let entry = canonicalize(&entry);
+            if let Some(display) = config.display {
+                self.displays.insert(entry.clone(), display);
+            }
+            if let Some(sort) = config.sort {
+                self.sort_as.insert(entry.clone(), sort);
+            }
+            if let Some(parent) = config.parent {
+                self.nest_under.insert(entry.clone(), parent);
+            }
+            for alias in config.aliases {
+                self.aliases.insert(canonicalize(&alias), entry.clone());
+            }
+            if let Some(see) = config.see {
+                self.see_instead.insert(entry.clone(), see);
+            }
+            if !config.see_also.is_empty() {
+                self.see_also
+                    .entry(entry.clone())
+                    .or_default()
+                    .extend(config.see_also);
+            }
+            if let Some(description) = config.description {
+                self.descriptions.insert(entry.clone(), description);
+            }
+            if let Some(link) = config.link {
+                self.links.insert(entry, link);
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the output backend to use for a renderer.
+    pub fn backend(&self, renderer: &str) -> Box<dyn IndexBackend> {
+        let name = if let Some(name) = self.backends.get(renderer) {
+            name
+        } else if self.skip_renderer.contains(renderer) {
+            "skip"
+        } else {
+            backend::default_for(renderer)
+        };
+        log::debug!("Using '{name}' backend for renderer '{renderer}'");
+        backend::by_name(name).expect("backend names are validated on construction")
+    }
+
+    /// Process a chapter, emitting index anchors and accumulating the index information.
+    pub fn process_chapter(
+        &self,
+        backend: &dyn IndexBackend,
+        path: &Option<PathBuf>,
+        part: Option<&str>,
+        name: &str,
+        content: &str,
+    ) -> String {
+        // Identifiers depend on all of the entries, so have to be recomputed once this chapter adds more.
+        self.entry_ids.borrow_mut().clear();
+        let (front_matter, body) = front_matter::split(content);
+        let front_matter = front_matter.unwrap_or_else(|e| {
+            log::error!("Ignoring invalid front matter in chapter '{name}': {e}");
+            FrontMatter::default()
+        });
+        for key in front_matter.unknown_keys() {
+            log::warn!("Ignoring unknown front matter key '{key}' in chapter '{name}'");
+        }
+        let excluded: HashSet<String> = front_matter
+            .exclude
+            .iter()
+            .map(|entry| canonicalize(entry))
+            .collect();
+        // Entries for the chapter as a whole become hidden markup at the top of the chapter.
+        let mut content = String::new();
+        for entry in &front_matter.index {
+            content += &format!("{{{{{HIDDEN}:{entry}}}}}\n\n");
+        }
+        content += body;
+        let content = content.as_str();
+
+        let mut count = 1;
+        INDEX_RE
+            .replace_all(content, |caps: &regex::Captures| {
+                if let Some(mat) = caps.get(0) {
+                    if mat.as_str().starts_with(ESCAPE_CHAR) {
+                        // Escaped directives are dealt with when directives are expanded.
+                        if DIRECTIVE_RE.is_match(mat.as_str()) {
+                            return mat.as_str().to_owned();
+                        }
+                        return mat.as_str()[1..].to_owned();
+                    }
+                }
+                let snippet = match caps.get(0) {
+                    Some(mat) if self.previews => snippet(content, mat.start(), mat.end()),
+                    _ => String::new(),
+                };
+                // Retrieve the content of the markup.  For a visible index entry, this is
+                // rendered in the output.
+                let viz = caps.name("viz").unwrap().as_str();
+                // Split off any reading of the text, which is only used for sorting.
+                let (content, reading) = split_reading(caps.name("content").unwrap().as_str());
+                let content = content.to_string();
+                // Remove any links from the index name and canonicalize whitespace to get
+                // what should appear in the index.
+                let index_entry = canonicalize(&content);
+                let index_entry = match self.aliases.get(&index_entry) {
+                    Some(entry) => entry.clone(),
+                    None => index_entry,
+                };
+                log::debug!("found {viz} index entry '{content}' which maps to '{index_entry}'");
+                if let Some(reading) = reading {
+                    self.add_reading(&index_entry, reading);
+                }
+                // Accumulate location against see_instead target if present
+                let target = match self.see_instead.get(&index_entry) {
+                    Some(dest) => {
+                        log::debug!("...or in fact '{dest}'");
+                        dest.clone()
+                    }
+                    None => index_entry.clone(),
+                };
+
+                let (visible, italic) = match viz {
+                    ITALIC => (true, true),
+                    VISIBLE => (true, false),
+                    GLOSSARY => (true, false),
+                    HIDDEN => (false, false),
+                    other => {
+                        eprintln!("Unexpected index type {other}!");
+                        (false, false)
+                    }
+                };
+                let mut marker = Marker {
+                    path: path.as_deref(),
+                    content: &content,
+                    entry: &index_entry,
+                    target: &target,
+                    visible,
+                    italic,
+                    anchor: None,
+                };
+                if excluded.contains(&index_entry) || excluded.contains(&target) {
+                    log::debug!("...which is excluded from the index in chapter '{name}'");
+                    return marker.text();
+                }
+                if viz == GLOSSARY {
+                    self.glossary_terms.borrow_mut().insert(target.clone());
+                }
+
+                marker.anchor = if backend.collects_locations() {
+                    let anchor = format!("a{:03}", count);
+                    let location = Location {
+                        path: path.clone(),
+                        part: part.map(str::to_owned),
+                        name: name.to_owned(),
+                        anchor: anchor.clone(),
+                        snippet,
+                    };
+                    count += 1;
+
+                    let mut entries = self.entries.borrow_mut();
+                    let itemlist = entries.entry(target.clone()).or_default();
+                    log::trace!("Index entry '{content}' found at {location:?}");
+                    itemlist.push(location);
+                    Some(anchor)
+                } else {
+                    None
+                };
+
+                backend.marker(self, &marker)
+            })
+            .to_string()
+    }
+
+    /// Indicate whether a chapter is excluded from indexing, by its name or path or because it is a draft.
+    fn excludes(&self, path: &Option<PathBuf>, name: &str) -> bool {
+        match path {
+            Some(path) => {
+                let path = path.to_string_lossy().replace('\\', "/");
+                self.exclude_chapters
+                    .iter()
+                    .any(|pattern| pattern == name || glob::matches(pattern, &path))
+            }
+            None => {
+                self.drafts == Drafts::Exclude
+                    || self.exclude_chapters.iter().any(|pattern| pattern == name)
+            }
+        }
+    }
+
+    /// Generate the index page.
+    pub fn generate_index(&self, backend: &dyn IndexBackend) -> String {
+        backend.index_page(self)
+    }
+
+    /// Generate the glossary page.
+    pub fn generate_glossary(&self, backend: &dyn IndexBackend) -> String {
+        backend.glossary_page(self)
+    }
+
+    /// Return the index entries collected so far (in sorted order), together with their locations.
+    pub fn entries(&self) -> Vec<(String, Vec<Location>)> {
+        self.scoped_entries(|_| true)
+    }
+
+    /// Indicate whether the book has a glossary.
+    fn has_glossary(&self) -> bool {
+        !self.glossary.is_empty() || !self.glossary_terms.borrow().is_empty()
+    }
+
+    /// Indicate whether entries in the index page need identifiers, because other pages link to them.
+    fn has_entry_ids(&self) -> bool {
+        self.has_glossary() || self.backlinks != Backlinks::None
+    }
+
+    /// Return the link from an occurrence of index markup to its entry in the index page, if enabled.
+    fn backlink(&self, marker: &Marker) -> Option<String> {
+        if self.backlinks == Backlinks::None {
+            return None;
+        }
+        let index_path = self.index_path.borrow();
+        let Some(index_path) = index_path.as_deref() else {
+            log::warn!("No index chapter to link '{}' to", marker.entry);
+            return None;
+        };
+        Some(format!(
+            "{}#{}",
+            relative_link(marker.path, index_path),
+            self.id_placeholder(marker.target)
+        ))
+    }
+
+    /// Return a placeholder for the identifier of `entry`, which can't be made unique until all entries are known.
+    fn id_placeholder(&self, entry: &str) -> String {
+        let mut targets = self.backlink_targets.borrow_mut();
+        let n = match targets.iter().position(|target| target == entry) {
+            Some(n) => n,
+            None => {
+                targets.push(entry.to_string());
+                targets.len() - 1
+            }
+        };
+        format!("<!--index-id:{n}-->")
+    }
+
+    /// Return the identifier of the anchor for `entry` in a generated page, which is unique among all entries.
+    fn anchor_id(&self, prefix: &str, entry: &str) -> String {
+        let mut ids = self.entry_ids.borrow_mut();
+        if ids.is_empty() {
+            *ids = self.unique_ids();
+        }
+        let id = ids.get(entry).map(String::as_str).unwrap_or(entry);
+        entry_id(prefix, id)
+    }
+
+    /// Return an identifier for each entry (or glossary term), adding a numeric suffix where entries would
+    /// otherwise have the same identifier.
+    fn unique_ids(&self) -> HashMap<String, String> {
+        let mut entries: HashSet<String> = self.entries.borrow().keys().cloned().collect();
+        entries.extend(self.see_instead.keys().cloned());
+        entries.extend(self.glossary.keys().cloned());
+        entries.extend(self.glossary_terms.borrow().iter().cloned());
+        let mut used = HashSet::new();
+        let mut ids = HashMap::new();
+        for entry in self.sorted(entries.into_iter().collect()) {
+            let base = entry_id("", &entry).trim_start_matches('-').to_string();
+            let mut id = base.clone();
+            let mut n = 2;
+            while !used.insert(id.clone()) {
+                id = if base.is_empty() {
+                    n.to_string()
+                } else {
+                    format!("{base}-{n}")
+                };
+                n += 1;
+            }
+            ids.insert(entry, id);
+        }
+        ids
+    }
+
+    /// Replace placeholders in the content of a chapter with the identifiers of the entries that backlinks
+    /// point to.
+    pub fn resolve_backlinks(&self, content: &str) -> String {
+        let targets = self.backlink_targets.borrow();
+        ID_RE
+            .replace_all(content, |caps: &regex::Captures| {
+                let target = caps["n"].parse().ok().and_then(|n: usize| targets.get(n));
+                match target {
+                    Some(target) => self.anchor_id("index-", target),
+                    None => String::new(),
+                }
+            })
+            .to_string()
+    }
+
+    /// Return the glossary terms in sorted order, together with their definitions.
+    fn glossary_entries(&self) -> Vec<(String, Option<String>)> {
+        let mut terms: HashSet<String> = self.glossary.keys().cloned().collect();
+        terms.extend(self.glossary_terms.borrow().iter().cloned());
+        self.sorted(terms.into_iter().collect())
+            .into_iter()
+            .map(|term| {
+                let definition = self
+                    .glossary
+                    .get(&term)
+                    .or_else(|| self.descriptions.get(&term))
+                    .cloned();
+                if definition.is_none() {
+                    log::warn!("Glossary term '{term}' has no definition");
+                }
+                (term, definition)
+            })
+            .collect()
+    }
+
+    /// Indicate whether an entry is a glossary term.
+    fn in_glossary(&self, entry: &str) -> bool {
+        self.glossary.contains_key(entry) || self.glossary_terms.borrow().contains(entry)
+    }
+
+    /// Sort entries alphabetically, ignoring case and special characters.
+    fn sorted(&self, mut keys: Vec<String>) -> Vec<String> {
+        // Need to sort twice:
+        // - once by key as-is, so uppercase entries come before lowercase entries
+        // - then by the collation order, which ignores case.
+        // This ensures that entries that are the same except for capitalization
+        // (e.g. "Borrow" and "borrow") always sort in a consistent order.
+        keys.sort();
+        let mut keyed: Vec<(String, String)> =
+            keys.into_iter().map(|s| (self.sort_key(&s), s)).collect();
+        keyed.sort_by(|(a, _), (b, _)| self.collation.compare(a, b));
+        keyed.into_iter().map(|(_, s)| s).collect()
+    }
+
+    /// Return the entries (in sorted order) that have locations in scope, together with those locations.
+    fn scoped_entries(&self, in_scope: impl Fn(&Location) -> bool) -> Vec<(String, Vec<Location>)> {
+        let entries = self.entries.borrow();
+        self.sorted(entries.keys().cloned().collect())
+            .into_iter()
+            .filter_map(|entry| {
+                let locations: Vec<Location> = entries[&entry]
+                    .iter()
+                    .filter(|loc| in_scope(loc))
+                    .cloned()
+                    .collect();
+                (!locations.is_empty()).then_some((entry, locations))
+            })
+            .collect()
+    }
+
+    /// Return the parts of the book that have index entries, in order, starting with `None` for any chapters
+    /// that precede the first part.
+    fn indexed_parts(&self) -> Vec<Option<String>> {
+        let entries = self.entries.borrow();
+        let mut parts = vec![None];
+        parts.extend(self.parts.borrow().iter().cloned().map(Some));
+        parts.retain(|part| {
+            entries
+                .values()
+                .flatten()
+                .any(|loc| loc.part.as_deref() == part.as_deref())
+        });
+        parts
+    }
+
+    /// Replace directives in the content of a chapter with a compact index of the chapter or part.
+    pub fn expand_directives(
+        &self,
+        backend: &dyn IndexBackend,
+        path: &Option<PathBuf>,
+        part: Option<&str>,
+        content: &str,
+    ) -> String {
+        DIRECTIVE_RE
+            .replace_all(content, |caps: &regex::Captures| {
+                if caps.name("escape").is_some() {
+                    return caps[0][1..].to_owned();
+                }
+                let entries = match &caps["scope"] {
+                    CHAPTER_SCOPE => self.scoped_entries(|loc| loc.path == *path),
+                    PART_SCOPE => self.scoped_entries(|loc| loc.part.as_deref() == part),
+                    other => {
+                        log::error!("Unexpected index directive scope {other}!");
+                        Vec::new()
+                    }
+                };
+                backend.scoped_index(self, &entries, path.as_deref())
+            })
+            .to_string()
+    }
+
+    /// Replace placeholders in the content of a chapter with navigation between the occurrences of each entry.
+    pub fn add_navigation(&self, path: &Option<PathBuf>, content: &str) -> String {
+        let entries = self.entries.borrow();
+        // Only locations in a chapter file can be navigated to, so locations in draft chapters are left out.
+        let navigable: HashMap<&String, Vec<&Location>> = entries
+            .iter()
+            .map(|(entry, locations)| {
+                (
+                    entry,
+                    locations.iter().filter(|loc| loc.path.is_some()).collect(),
+                )
+            })
+            .collect();
+        // Find the entry, and the position within its locations, for each anchor in this chapter.
+        let mut anchors = HashMap::new();
+        for (entry, locations) in navigable.iter() {
+            for (idx, loc) in locations.iter().enumerate() {
+                if loc.path == *path {
+                    anchors.insert(loc.anchor.as_str(), (*entry, idx));
+                }
+            }
+        }
+        let link = |text: &str, loc: Option<&&Location>| match loc {
+            Some(Location {
+                path: Some(to),
+                anchor,
+                ..
+            }) => format!("[{text}]({}#{anchor})", relative_link(path.as_deref(), to)),
+            _ => text.to_string(),
+        };
+        let result = NAV_RE.replace_all(content, |caps: &regex::Captures| {
+            let Some((entry, idx)) = anchors.get(&caps["anchor"]) else {
+                return String::new();
+            };
+            let locations = &navigable[entry];
+            let previous = idx.checked_sub(1).and_then(|prev| locations.get(prev));
+            format!(
+                "<span class=\"index-nav\">({} — {} / {})</span>",
+                self.strings.occurrence(idx + 1, locations.len(), entry),
+                link(&self.strings.previous, previous),
+                link(&self.strings.next, locations.get(idx + 1)),
+            )
+        });
+        if result == content {
+            content.to_string()
+        } else {
+            NAV_STYLE.to_string() + &result
+        }
+    }
+
+    /// Return the locations of `entry` that are in `section`, or `None` if there are no such locations.
+    fn section_locations(&self, entry: &str, section: Section) -> Option<Vec<Location>> {
+        let entries = self.entries.borrow();
+        let locations = entries.get(entry)?;
+        let Some(part) = section else {
+            return Some(locations.clone());
+        };
+        let locations: Vec<Location> = locations
+            .iter()
+            .filter(|loc| loc.part.as_deref() == part)
+            .cloned()
+            .collect();
+        (!locations.is_empty()).then_some(locations)
+    }
+
+    /// Return the top-level entries in sorted order (restricted to those with locations in `section`), together with
+    /// the sorted sub-entries for each entry that has any.
+    fn sorted_entries(&self, section: Section) -> (Vec<String>, SubEntries) {
+        let mut keys: HashSet<String> = self
+            .entries
+            .borrow()
+            .keys()
+            .filter(|entry| self.section_locations(entry, section).is_some())
+            .cloned()
+            .collect();
+        // A see-instead entry goes wherever the entry that it refers to is.
+        keys.extend(
+            self.see_instead
+                .iter()
+                .filter(|(_, alt)| {
+                    section.is_none() || self.section_locations(alt, section).is_some()
+                })
+                .map(|(entry, _)| entry.clone()),
+        );
+
+        // Place each entry according to its levels, so that the tree matches the nesting that backends which use
+        // `levels()` directly produce.  The entries above an entry are included even if they have no locations of
+        // their own, so that none of their sub-entries are lost.
+        let mut chains = HashSet::new();
+        let mut top = HashSet::new();
+        let mut sub_entries = SubEntries::new();
+        for key in &keys {
+            let levels: Vec<String> = self.levels(key).into_iter().map(str::to_string).collect();
+            for len in 1..=levels.len() {
+                if !chains.insert(levels[..len].to_vec()) {
+                    continue;
+                }
+                if len == 1 {
+                    top.insert(levels[0].clone());
+                } else {
+                    let head = levels[..len - 1].to_vec();
+                    sub_entries
+                        .entry(head)
+                        .or_default()
+                        .push(levels[len - 1].clone());
+                }
+            }
+        }
+        for subs in sub_entries.values_mut() {
+            *subs = self.sorted(std::mem::take(subs));
+        }
+        (self.sorted(top.into_iter().collect()), sub_entries)
+    }
+
+    /// Indicate whether `chain` (of entries from the top level down) is where the last entry in it is placed in the
+    /// index, rather than being only a heading for sub-entries that are nested under it.
+    fn is_placed(&self, chain: &[String]) -> bool {
+        chain
+            .last()
+            .is_some_and(|entry| self.levels(entry).iter().eq(chain.iter()))
+    }
+
+    /// Return the top-level entries in sorted order (restricted to those with locations in `section`), split into
+    /// groups with a label for each group (or a single unlabelled group if `group_headings` is not set), together
+    /// with the sorted sub-entries for each entry that has any.
+    fn grouped_entries(&self, section: Section) -> (Vec<Group>, SubEntries) {
+        let (keys, sub_entries) = self.sorted_entries(section);
+        if !self.group_headings {
+            return (vec![(None, keys)], sub_entries);
+        }
+        let mut groups: Vec<Group> = Vec::new();
+        for key in keys {
+            let label = self.group_label(&self.sort_key(&key));
+            match groups.last_mut() {
+                Some((Some(last), entries)) if self.collation.same_group(last, &label) => {
+                    entries.push(key)
+                }
+                _ => groups.push((Some(label), vec![key])),
+            }
+        }
+        (groups, sub_entries)
+    }
+
+    /// Return the label of the group that an entry with the given sort key belongs in.
+    fn group_label(&self, sort_key: &str) -> String {
+        match sort_key.chars().next() {
+            Some(c) if kana_row(c).is_some() => kana_row(c).unwrap_or_default().to_string(),
+            Some(c) if c.is_alphabetic() => c.to_uppercase().to_string(),
+            Some(c) if c.is_numeric() => self.strings.numbers.clone(),
+            _ => self.strings.symbols.clone(),
+        }
+    }
+
+    /// Return the key that determines the sort order of an entry.
+    fn sort_key(&self, entry: &str) -> String {
+        if let Some(sort) = self.sort_as.get(entry) {
+            return sort.clone();
+        }
+        if let Some(reading) = self.readings.borrow().get(entry) {
+            return to_hiragana(reading);
+        }
+        self.sort_keys.key(entry)
+    }
+
+    /// Record the reading of an entry.
+    fn add_reading(&self, entry: &str, reading: &str) {
+        let mut readings = self.readings.borrow_mut();
+        match readings.get(entry) {
+            Some(existing) if existing != reading => {
+                log::warn!(
+                    "Ignoring reading '{reading}' of '{entry}', already read as '{existing}'"
+                )
+            }
+            Some(_) => {}
+            None => {
+                readings.insert(entry.to_string(), reading.to_string());
+            }
+        }
+    }
+
+    /// Return the chain of entries from a top-level entry down to `entry`, following `nest_under`.  An entry that
+    /// would be more than [`MAX_LEVELS`] levels deep is lifted up to the deepest ancestor that can hold it.
+    fn levels<'a>(&'a self, entry: &'a str) -> Vec<&'a str> {
+        let mut levels = vec![entry];
+        while let Some(head) = self.nest_under.get(levels[0]) {
+            // Cycles are rejected on construction, but don't loop forever on one.
+            if levels.contains(&head.as_str()) {
+                break;
+            }
+            levels.insert(0, head);
+        }
+        if levels.len() > MAX_LEVELS {
+            levels.drain(MAX_LEVELS - 1..levels.len() - 1);
+        }
+        levels
+    }
+
+    /// Check the nesting of entries, failing if an entry is (indirectly) nested under itself, and warning about entries
+    /// that are nested too deeply to be placed where configured.
+    fn check_nesting(&self) -> Result<(), Error> {
+        let mut entries: Vec<&String> = self.nest_under.keys().collect();
+        entries.sort();
+        for entry in entries {
+            // The chain of entries from `entry` upwards.
+            let mut chain = vec![entry.as_str()];
+            while let Some(head) = self.nest_under.get(chain[chain.len() - 1]) {
+                let cycle = chain.contains(&head.as_str());
+                chain.push(head);
+                if cycle {
+                    return Err(Error::msg(format!(
+                        "Index entry nested under itself: {}",
+                        chain.join(" => ")
+                    )));
+                }
+            }
+            if chain.len() > MAX_LEVELS {
+                log::warn!(
+                    "Index entry '{entry}' is nested more than {MAX_LEVELS} levels deep, so is placed under '{}'",
+                    chain[chain.len() + 1 - MAX_LEVELS]
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Return the display forms of the levels of an entry, from the top-level entry downwards.
+    fn level_displays<'a>(&'a self, entry: &'a str) -> Vec<&'a str> {
+        let levels = self.levels(entry);
+        let mut displays = vec![self.display(levels[0])];
+        for pair in levels.windows(2) {
+            displays.push(self.subentry(pair[0], pair[1]));
+        }
+        displays
+    }
+
+    /// Return the display form of a top-level entry.
+    fn display<'a>(&'a self, entry: &'a str) -> &'a str {
+        self.displays.get(entry).map_or(entry, String::as_str)
+    }
+
+    /// Generate the display form of a sub-entry.
+    fn subentry<'a>(&'a self, entry: &'_ str, sub: &'a str) -> &'a str {
+        if let Some(display) = self.displays.get(sub) {
+            return display;
+        }
+        if self.suppress_head {
+            // See if the sub-entry starts with "{entry}, ".
+            if let Some(rest) = sub.strip_prefix(entry) {
+                if let Some(inner_sub) = rest.strip_prefix(", ") {
+                    return inner_sub;
+                }
+            }
+        }
+        sub
+    }
+
+    /// Indicate whether a renderer is supported.
+    pub fn supports_renderer(renderer: &str) -> bool {
+        renderer != "not-supported"
+    }
+}
+
+impl Preprocessor for Index {
+    fn name(&self) -> &str {
+        NAME
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book, Error> {
+        let backend = self.backend(&ctx.renderer);
+        let skip = backend::by_name("skip").expect("skip backend exists");
+        let is_index = |name: &str| name == "Index" || name == self.strings.title;
+        // A chapter named "Glossary" is only replaced when there is a glossary to generate, so that a hand-written
+        // glossary is indexed like any other chapter.
+        let generates_glossary = !self.glossary.is_empty()
+            || book.iter().any(|item| {
+                matches!(item, BookItem::Chapter(chap) if has_glossary_markup(&chap.content))
+            });
+        let is_glossary = |name: &str| {
+            generates_glossary && (name == "Glossary" || name == self.strings.glossary)
+        };
+
+        // Find the generated chapters first, so that other chapters can link to them.
+        for item in book.iter() {
+            if let BookItem::Chapter(chap) = item {
+                if is_index(&chap.name) {
+                    *self.index_path.borrow_mut() = chap.path.clone();
+                } else if is_glossary(&chap.name) {
+                    *self.glossary_path.borrow_mut() = chap.path.clone();
+                }
+            }
+        }
+
+        // Collect the locations of index entries from all chapters before generating anything from them.
+        let mut part = None;
+        book.for_each_mut(|item| match item {
+            BookItem::PartTitle(title) => {
+                self.parts.borrow_mut().push(title.clone());
+                part = Some(title.clone());
+            }
+            BookItem::Chapter(chap) if is_index(&chap.name) || is_glossary(&chap.name) => {}
+            BookItem::Chapter(chap) if self.excludes(&chap.path, &chap.name) => {
+                log::info!("Not indexing excluded chapter '{}'", chap.name);
+                // Markup is still removed from excluded chapters, without recording any locations.
+                chap.content = self.process_chapter(
+                    skip.as_ref(),
+                    &chap.path,
+                    part.as_deref(),
+                    &chap.name,
+                    &chap.content,
+                );
+            }
+            BookItem::Chapter(chap) => {
+                if chap.path.is_none() && self.drafts == Drafts::Warn {
+                    log::warn!(
+                        "Indexing draft chapter '{}', whose locations will not be linked",
+                        chap.name
+                    );
+                }
+                log::info!("Indexing chapter '{}'", chap.name);
+                chap.content = self.process_chapter(
+                    backend.as_ref(),
+                    &chap.path,
+                    part.as_deref(),
+                    &chap.name,
+                    &chap.content,
+                );
+            }
+            _ => {}
+        });
+
+        if let Some(file) = &self.search_terms {
+            // The search only exists in HTML output.
+            if ctx.renderer == "html" {
+                // This is where mdbook looks for the `additional-js` files of the HTML output, and it keeps
+                // generated output out of the source directory.
+                let path = ctx.root.join(file);
+                match search::write_if_changed(&path, &search::search_script(self)) {
+                    Ok(true) => log::info!("Wrote index entries for search to {}", path.display()),
+                    Ok(false) => log::debug!("Index entries for search are unchanged"),
+                    Err(e) => log::error!("Failed to write {}: {e}", path.display()),
+                }
+            }
+        }
+
+        let mut part = None;
+        book.for_each_mut(|item| match item {
+            BookItem::PartTitle(title) => part = Some(title.clone()),
+            BookItem::Chapter(chap) if is_index(&chap.name) => {
+                log::debug!("Replacing chapter named '{}' with contents", chap.name);
+                chap.content = self.generate_index(backend.as_ref());
+            }
+            BookItem::Chapter(chap) if is_glossary(&chap.name) => {
+                log::debug!("Replacing chapter named '{}' with glossary", chap.name);
+                chap.content = self.generate_glossary(backend.as_ref());
+            }
+            BookItem::Chapter(chap) => {
+                chap.content = self.expand_directives(
+                    backend.as_ref(),
+                    &chap.path,
+                    part.as_deref(),
+                    &chap.content,
+                );
+                if self.backlinks != Backlinks::None {
+                    chap.content = self.resolve_backlinks(&chap.content);
+                }
+                if self.occurrence_nav {
+                    chap.content = self.add_navigation(&chap.path, &chap.content);
+                }
+            }
+            _ => {}
+        });
+        Ok(book)
+    }
+
+    fn supports_renderer(&self, renderer: &str) -> Result<bool, Error> {
+        Ok(Self::supports_renderer(renderer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        use super::canonicalize;
+        let cases = vec![
+            ("abc", "abc"),
+            ("ab cd", "ab cd"),
+            ("ab    cd", "ab cd"),
+            ("ab    cd", "ab cd"),
+            ("ab  	cd", "ab cd"),
+            ("ab  \ncd", "ab cd"),
+            ("`ab`", "`ab`"),
+            ("[`ab`](somedest)", "`ab`"),
+            ("[`ab`]", "[`ab`]"),
+            ("[`ab    cd`](somedest)", "`ab cd`"),
+        ];
+        for (input, want) in cases {
+            let got = canonicalize(input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_snippet() {
+        let cases = [
+            (
+                "A {{i:borrow}} is short.",
+                "{{i:borrow}}",
+                "A borrow is short.",
+            ),
+            (
+                "First paragraph.\n\nThe *rules* for {{i:`&mut`}}\n[references](ch2.md).\n\nLast.",
+                "{{i:`&mut`}}",
+                "The rules for &mut references.",
+            ),
+            (
+                "Text {{hi:hidden}}{{i:所有権@しょゆうけん}} and \\{{i:escaped}} text.",
+                "{{i:所有権@しょゆうけん}}",
+                "Text 所有権 and {{i:escaped}} text.",
+            ),
+            (
+                "This sentence has quite a lot of words before we get to the {{i:entry}} and then \
+                 also rather a lot of words after the entry.",
+                "{{i:entry}}",
+                "…a lot of words before we get to the entry and then also rather a lot of words…",
+            ),
+            // No spaces to truncate at.
+            (
+                "あいうえおあいうえおあいうえおあいうえおあいうえおあいうえおあいうえおあいうえおあいうえおあいうえお{{i:所有権}}かきくけこかきくけこかきくけこかきくけこかきくけこかきくけこかきくけこかきくけこかきくけこかきくけこ",
+                "{{i:所有権}}",
+                "…あいうえおあいうえおあいうえおあいうえおあいうえおあいうえおあいうえおあいうえお所有権かきくけこかきくけこかきくけこかきくけこかきくけこかきくけこかきくけこかきくけこ…",
+            ),
+        ];
+        for (content, markup, want) in cases {
+            let start = content.find(markup).unwrap();
+            let got = snippet(content, start, start + markup.len());
+            assert_eq!(got, want, "Mismatch for input: {}", content);
+        }
+    }
+
+    #[test]
+    fn test_entry_id() {
+        let cases = [
+            ("borrow", "index-borrow"),
+            ("Borrow checker", "index-Borrow-checker"),
+            ("`&mut`", "index-mut"),
+            ("`Vec<T>`", "index-Vec-T"),
+            ("所有権", "index-所有権"),
+        ];
+        for (input, want) in cases {
+            let got = entry_id("index-", input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_locator_runs() {
+        let loc = |path: Option<&str>| Location {
+            path: path.map(PathBuf::from),
+            part: None,
+            name: "Chapter".to_string(),
+            anchor: "a001".to_string(),
+            snippet: String::new(),
+        };
+        let cases = [
+            (vec![], vec![]),
+            (vec![Some("ch1.md")], vec![(0, 0)]),
+            (vec![Some("ch1.md"), Some("ch1.md")], vec![(0, 0), (1, 1)]),
+            (
+                vec![
+                    Some("ch1.md"),
+                    Some("ch1.md"),
+                    Some("ch1.md"),
+                    Some("ch2.md"),
+                ],
+                vec![(0, 2), (3, 3)],
+            ),
+            (vec![None, None, None], vec![(0, 0), (1, 1), (2, 2)]),
+        ];
+        for (input, want) in cases {
+            let locations: Vec<Location> = input.iter().map(|path| loc(*path)).collect();
+            let got = locator_runs(&locations);
+            assert_eq!(got, want, "Mismatch for input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_relative_link() {
+        let cases = [
+            (None, "ch1.md", "ch1.md"),
+            (Some("ch1.md"), "ch2.md", "ch2.md"),
+            (Some("ch1.md"), "part/ch2.md", "part/ch2.md"),
+            (Some("part/ch2.md"), "ch1.md", "../ch1.md"),
+            (Some("a/b/ch3.md"), "a/c/ch4.md", "../c/ch4.md"),
+            (Some("a/ch3.md"), "a/ch3.md", "ch3.md"),
+        ];
+        for (from, to, want) in cases {
+            let got = relative_link(from.map(Path::new), Path::new(to));
+            assert_eq!(got, want, "Mismatch for link from {:?} to {}", from, to);
+        }
+    }
+
+    #[test]
+    fn test_expand_directives() {
+        let index = Index::default();
+        let chapters = [
+            (
+                "ch1.md",
+                None,
+                "{{i:borrow}} {{i:move}} \\{{index:chapter}}",
+            ),
+            ("part1/ch2.md", Some("Part I"), "{{i:Borrow}} {{hi:move}}"),
+            ("part1/ch3.md", Some("Part I"), "{{i:lifetime}}"),
+        ];
+        let backend = backend::by_name("html").unwrap();
+        let mut processed = Vec::new();
+        for (path, part, content) in chapters {
+            let path = Some(PathBuf::from(path));
+            let got = index.process_chapter(backend.as_ref(), &path, part, "Chapter", content);
+            processed.push((path, part, got));
+        }
+        assert!(processed[0].2.ends_with(" \\{{index:chapter}}"));
+
+        let cases = [
+            (
+                0,
+                "{{index:chapter}}",
+                "borrow, [1](ch1.md#a001)<br/>\nmove, [1](ch1.md#a002)<br/>\n",
+            ),
+            (
+                1,
+                "{{index:part}}",
+                "Borrow, [1](ch2.md#a001)<br/>\n\
+                 lifetime, [1](ch3.md#a001)<br/>\n\
+                 move, [1](ch2.md#a002)<br/>\n",
+            ),
+            (
+                2,
+                "{{ index:chapter }} {{index: part}}",
+                "{{ index:chapter }} Borrow, [1](ch2.md#a001)<br/>\n\
+                 lifetime, [1](ch3.md#a001)<br/>\n\
+                 move, [1](ch2.md#a002)<br/>\n",
+            ),
+            (0, "\\{{index:chapter}}", "{{index:chapter}}"),
+        ];
+        for (idx, input, want) in cases {
+            let (path, part, _) = &processed[idx];
+            let got = index.expand_directives(backend.as_ref(), path, *part, input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_add_navigation() {
+        let index = Index {
+            occurrence_nav: true,
+            ..Default::default()
+        };
+        let backend = backend::by_name("html").unwrap();
+        let ch1 = Some(PathBuf::from("ch1.md"));
+        let ch2 = Some(PathBuf::from("part/ch2.md"));
+        let content1 = index.process_chapter(
+            backend.as_ref(),
+            &ch1,
+            None,
+            "One",
+            "{{i:x}} {{hi:y}} {{i:x}}",
+        );
+        // Draft chapters share anchors, so get no navigation, and are skipped by the navigation of other chapters.
+        for name in ["Draft 1", "Draft 2"] {
+            let draft = index.process_chapter(backend.as_ref(), &None, None, name, "{{i:x}}");
+            assert_eq!(draft, "<a name=\"a001\"></a>x");
+            assert_eq!(index.add_navigation(&None, &draft), draft);
+        }
+        let content2 = index.process_chapter(backend.as_ref(), &ch2, None, "Two", "{{i:x}}");
+        assert_eq!(content2, "<a name=\"a001\"></a><!--index-nav:a001-->x");
+
+        let got = index.add_navigation(&ch1, &content1);
+        let want = NAV_STYLE.to_string()
+            + "<a name=\"a001\"></a><span class=\"index-nav\">(occurrence 1 of 3 of 'x' — previous / \
+               [next](ch1.md#a003))</span>x \
+               <a name=\"a002\"></a><span class=\"index-nav\">(occurrence 1 of 1 of 'y' — previous / next)</span> \
+               <a name=\"a003\"></a><span class=\"index-nav\">(occurrence 2 of 3 of 'x' — \
+               [previous](ch1.md#a001) / [next](part/ch2.md#a001))</span>x";
+        assert_eq!(got, want);
+
+        let got = index.add_navigation(&ch2, &content2);
+        let want = NAV_STYLE.to_string()
+            + "<a name=\"a001\"></a><span class=\"index-nav\">(occurrence 3 of 3 of 'x' — \
+               [previous](../ch1.md#a003) / next)</span>x";
+        assert_eq!(got, want);
+        assert_eq!(index.add_navigation(&ch2, "no markup"), "no markup");
+    }
+
+    #[test]
+    fn test_process_chapter() {
+        let index = Index::default();
+        let input = "Some {{i:visible}}, {{ii:italic}} and {{hi:hidden}} text.";
+        let cases = [
+            (
+                "html",
+                "Some <a name=\"a001\"></a>visible, <a name=\"a002\"></a>*italic* and <a name=\"a003\"></a> text.",
+            ),
+            ("skip", "Some visible, *italic* and  text."),
+            (
+                "latex",
+                "Some `\\index{visible}`{=latex}visible, `\\index{italic}`{=latex}*italic* and `\\index{hidden}`{=latex} text.",
+            ),
+        ];
+        for (name, want) in cases {
+            let backend = backend::by_name(name).unwrap();
+            let got = index.process_chapter(backend.as_ref(), &None, None, "Chapter", input);
+            assert_eq!(got, want, "Mismatch for backend: {}", name);
+        }
+    }
+
+    #[test]
+    fn test_process_chapter_front_matter() {
+        let index = Index::default();
+        let backend = backend::by_name("html").unwrap();
+        let cases = [
+            (
+                "+++\nindex = [\"unsafe\", \"raw pointer\"]\n+++\n# Title\n\n{{i:unsafe}} code",
+                "<a name=\"a001\"></a>\n\n<a name=\"a002\"></a>\n\n# Title\n\n<a name=\"a003\"></a>unsafe code",
+            ),
+            (
+                "+++\nexclude = [\"Unsafe\"]\n+++\n{{i:Unsafe}} {{hi:Unsafe}} {{i:raw pointer}}",
+                "Unsafe  <a name=\"a001\"></a>raw pointer",
+            ),
+            ("+++\nindexes = [\"unsafe\"]\n+++\ntext", "text"),
+        ];
+        for (input, want) in cases {
+            let got = index.process_chapter(backend.as_ref(), &None, None, "Chapter", input);
+            assert_eq!(got, want, "Mismatch for input: {}", input);
+        }
+        let entries = index.entries.borrow();
+        assert_eq!(entries["unsafe"].len(), 2);
+        assert_eq!(entries["raw pointer"].len(), 2);
+    }
+
+    #[test]
+    fn test_excludes() {
+        let index = Index {
+            exclude_chapters: vec![
+                "CHANGELOG.md".to_string(),
+                "api/**".to_string(),
+                "Appendix".to_string(),
+            ],
+            drafts: Drafts::Exclude,
+            ..Default::default()
+        };
+        let cases = [
+            (Some("intro.md"), "Introduction", false),
+            (Some("CHANGELOG.md"), "Changes", true),
+            (Some("api/types/index.md"), "Types", true),
+            (Some("apis.md"), "APIs", false),
+            (Some("appendix.md"), "Appendix", true),
+            (None, "Draft", true),
+        ];
+        for (path, name, want) in cases {
+            let path = path.map(PathBuf::from);
+            let got = index.excludes(&path, name);
+            assert_eq!(got, want, "Mismatch for input: {:?} {}", path, name);
+        }
+        let index = Index::default();
+        assert!(!index.excludes(&None, "Draft"));
+    }
+
+    #[test]
+    fn test_backend_selection() {
+        let index = Index {
+            skip_renderer: HashSet::from(["markdown".to_string(), "epub".to_string()]),
+            backends: HashMap::from([("epub".to_string(), "latex".to_string())]),
+            ..Default::default()
+        };
+        let input = "{{i:entry}}";
+        let cases = [
+            ("html", "<a name=\"a001\"></a>entry"),
+            ("markdown", "entry"),
+            ("custom", "entry"),
+            ("epub", "`\\index{entry}`{=latex}entry"),
+            ("latex", "`\\index{entry}`{=latex}entry"),
+        ];
+        for (renderer, want) in cases {
+            let backend = index.backend(renderer);
+            let got = index.process_chapter(backend.as_ref(), &None, None, "Chapter", input);
+            assert_eq!(got, want, "Mismatch for renderer: {}", renderer);
+        }
+    }
+
+    #[test]
+    fn test_levels() {
+        let index = Index {
+            nest_under: HashMap::from([
+                ("test, unit".to_string(), "test".to_string()),
+                ("test, unit, doc".to_string(), "test, unit".to_string()),
+                (
+                    "test, unit, doc, more".to_string(),
+                    "test, unit, doc".to_string(),
+                ),
+                ("loop".to_string(), "loop".to_string()),
+            ]),
+            suppress_head: true,
+            ..Default::default()
+        };
+        let cases = [
+            ("test", vec!["test"]),
+            ("test, unit", vec!["test", "unit"]),
+            ("test, unit, doc", vec!["test", "unit", "doc"]),
+            // Too deep, so lifted up to the deepest level.
+            ("test, unit, doc, more", vec!["test", "unit", "doc, more"]),
+            ("loop", vec!["loop"]),
+        ];
+        for (entry, want) in cases {
+            let got = index.level_displays(entry);
+            assert_eq!(got, want, "Mismatch for entry: {}", entry);
+        }
+    }
+
+    #[test]
+    fn test_sort_as() {
+        let index = Index {
+            readings: RefCell::new(HashMap::from([("型".to_string(), "かた".to_string())])),
+            sort_as: HashMap::from([("型".to_string(), "zzz".to_string())]),
+            descriptions: HashMap::from([("型".to_string(), "A type.".to_string())]),
+            ..Default::default()
+        };
+        // An explicit sort key takes precedence over a reading.
+        let got = index.sorted(vec!["型".to_string(), "b".to_string(), "a".to_string()]);
+        assert_eq!(got, vec!["a", "b", "型"]);
+        // Descriptions don't make a glossary.
+        assert!(!index.has_glossary());
+    }
+
+    #[test]
+    fn test_grouped_entries() {
+        let index = Index {
+            collation: Collation::new("ja", false),
+            group_headings: true,
+            strings: Strings::for_language(Some("ja")),
+            readings: RefCell::new(HashMap::from([("型".to_string(), "カタ".to_string())])),
+            ..Default::default()
+        };
+        let backend = backend::by_name("html").unwrap();
+        let got = index.process_chapter(
+            backend.as_ref(),
+            &None,
+            None,
+            "Chapter",
+            "{{i:所有権@しょゆうけん}} {{i:借用@しゃくよう}} {{i:ライフタイム}} {{hi:型}} \
+             {{i:`&mut`}} {{i:2D}} {{i:borrow}} {{i:Box}}",
+        );
+        assert!(got.starts_with("<a name=\"a001\"></a>所有権 <a name=\"a002\"></a>借用 "));
+        let want = vec![
+            (Some("記号".to_string()), vec!["`&mut`".to_string()]),
+            (Some("数字".to_string()), vec!["2D".to_string()]),
+            (
+                Some("B".to_string()),
+                vec!["borrow".to_string(), "Box".to_string()],
+            ),
+            (Some("か行".to_string()), vec!["型".to_string()]),
+            (
+                Some("さ行".to_string()),
+                vec!["借用".to_string(), "所有権".to_string()],
+            ),
+            (Some("ら行".to_string()), vec!["ライフタイム".to_string()]),
+        ];
+        assert_eq!(index.grouped_entries(None).0, want);
+    }
+
+    #[test]
+    fn test_matches() {
+        let tests = [
+            ("blah {{i:simple}} blah", VISIBLE, "simple"),
+            ("blah{{i:simple}}blah", VISIBLE, "simple"),
+            ("blah {{hi:simple}} blah", HIDDEN, "simple"),
+            ("blah {{ii:simple}} blah", ITALIC, "simple"),
+            (
+                "blah {{i:[link](http://example.com)}} blah",
+                VISIBLE,
+                "[link](http://example.com)",
+            ),
+            ("blah {{i:*italic*}} blah", VISIBLE, "*italic*"),
+            ("blah {{i:_italic_}} blah", VISIBLE, "_italic_"),
+            ("blah {{i:`code`}} blah", VISIBLE, "`code`"),
+            ("blah {{i:first}} blah {{hi:second}}", VISIBLE, "first"),
+            ("blah {{i:interior space}} blah", VISIBLE, "interior space"),
+            (
+                "blah {{i:interior\nnewline}} blah",
+                VISIBLE,
+                "interior\nnewline",
+            ),
+            (
+                "blah {{i:interior\tspace}} blah",
+                VISIBLE,
+                "interior\tspace",
+            ),
+            ("blah {{i: leading space}} blah", VISIBLE, "leading space"),
+            (
+                "blah {{i:trailing space }} blah",
+                VISIBLE,
+                "trailing space ",
+            ),
+            ("blah {{i:normal}} blah \\{{i:escaped}}", VISIBLE, "normal"),
+        ];
+        for (input, want_viz, want_content) in tests {
+            let got = INDEX_RE.captures_iter(input).next().unwrap();
+            let got_viz = got.name("viz").unwrap().as_str();
+            assert_eq!(got_viz, want_viz, "for input '{input}'");
+            let got_content = got.name("content").unwrap().as_str();
+            assert_eq!(got_content, want_content, "for input '{input}'");
+        }
+    }
+
+    #[test]
+    fn test_escaped_matches() {
+        let tests = [
+            "blah \\{{i:simple}} blah",
+            "blah\\{{i:simple}}blah",
+            "blah \\{{hi:simple}} blah",
+            "blah \\{{ii:simple}} blah",
+            "blah \\{{i:`code`}} blah",
+            "blah \\{{i:interior space}} blah",
+            "blah \\{{i:interior\nnewline}} blah",
+            "blah \\{{i: leading space}} blah",
+            "blah \\{{i:trailing space }} blah",
+        ];
+        for input in tests {
+            let got = INDEX_RE.captures_iter(input).next().unwrap();
+            assert!(
+                got.get(0).unwrap().as_str().starts_with(ESCAPE_CHAR),
+                "got {:?} for input '{}'",
+                got,
+                input
+            );
+            assert!(got.name("viz").is_none(), "for input '{}'", input);
+            assert!(got.name("content").is_none(), "for input '{}'", input);
+        }
+    }
+
+    #[test]
+    fn test_escaped_and_unescaped() {
+        let input = "blah \\{{i:escaped}} blah {{i:second}}";
+        let mut iter = INDEX_RE.captures_iter(input);
+        let got1 = iter.next().unwrap();
+        assert!(got1.get(0).unwrap().as_str().starts_with(ESCAPE_CHAR),);
+        let got2 = iter.next().unwrap();
+        let got2_viz = got2.name("viz").unwrap().as_str();
+        assert_eq!(got2_viz, VISIBLE);
+        let got2_content = got2.name("content").unwrap().as_str();
+        assert_eq!(got2_content, "second");
+    }
+}
@@ -0,0 +1,45 @@
+// Show index entries that match the search query ahead of mdbook's own search results.
+(function () {
+    "use strict";
+    const bar = document.getElementById("mdbook-searchbar") || document.getElementById("searchbar");
+    const results =
+        document.getElementById("mdbook-searchresults") || document.getElementById("searchresults");
+    if (!bar || !results) {
+        return;
+    }
+    const root = typeof path_to_root === "undefined" ? "" : path_to_root;
+    const observer = new MutationObserver(update);
+
+    function update() {
+        // Don't react to our own changes to the results.
+        observer.disconnect();
+        for (const old of results.querySelectorAll(".index-term-result")) {
+            old.remove();
+        }
+        const query = bar.value.trim().toLowerCase();
+        if (query.length >= 2) {
+            const matches = window.mdbookIndexTerms.filter((entry) =>
+                entry.term.toLowerCase().includes(query)
+            );
+            for (const entry of matches.reverse()) {
+                const item = document.createElement("li");
+                item.className = "index-term-result";
+                const term = document.createElement("strong");
+                term.textContent = entry.term;
+                item.appendChild(term);
+                for (const loc of entry.locations) {
+                    item.appendChild(document.createTextNode(", "));
+                    const link = document.createElement("a");
+                    link.href = root + loc.href;
+                    link.textContent = loc.chapter;
+                    item.appendChild(link);
+                }
+                results.prepend(item);
+            }
+        }
+        observer.observe(results, { childList: true });
+    }
+
+    bar.addEventListener("input", update);
+    observer.observe(results, { childList: true });
+})();
@@ -0,0 +1,87 @@
+//! Data for including index entries in the results of the HTML search.
+
+use crate::{plain_text, relative_link, Index};
+use serde_json::json;
+use std::{fs, io, path::Path};
+
+/// Script that shows matching index entries in the search results.
+const SEARCH_SCRIPT: &str = include_str!("search.js");
+
+/// Generate a script that holds the index entries and their locations, and shows them in the search results.
+pub(crate) fn search_script(index: &Index) -> String {
+    let entries = index.entries.borrow();
+    let terms: Vec<_> = index
+        .sorted(entries.keys().cloned().collect())
+        .into_iter()
+        .map(|entry| {
+            let locations: Vec<_> = entries[&entry]
+                .iter()
+                .filter_map(|loc| {
+                    let path = loc.path.as_ref()?.with_extension("html");
+                    Some(json!({
+                        "chapter": loc.name,
+                        "href": format!("{}#{}", relative_link(None, &path), loc.anchor),
+                    }))
+                })
+                .collect();
+            json!({ "term": plain_text(&entry), "locations": locations })
+        })
+        .collect();
+    format!(
+        "window.mdbookIndexTerms = {};\n\n{SEARCH_SCRIPT}",
+        serde_json::Value::Array(terms)
+    )
+}
+
+/// Write `contents` to the file at `path`, unless it already holds them.
+///
+/// The file is listed in the `additional-js` of the HTML output, which `mdbook serve` watches, so leaving it untouched
+/// avoids triggering another build.
+pub(crate) fn write_if_changed(path: &Path, contents: &str) -> io::Result<bool> {
+    if fs::read_to_string(path).is_ok_and(|existing| existing == contents) {
+        return Ok(false);
+    }
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, contents)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_search_script() {
+        let index = Index::default();
+        let backend = backend::by_name("html").unwrap();
+        index.process_chapter(
+            backend.as_ref(),
+            &Some(PathBuf::from("dir/ch1.md")),
+            None,
+            "Chapter One",
+            "{{i:`borrow` checker}} {{hi:*move*}}",
+        );
+        let got = search_script(&index);
+        let want = "window.mdbookIndexTerms = [\
+                    {\"locations\":[{\"chapter\":\"Chapter One\",\"href\":\"dir/ch1.html#a001\"}],\
+                    \"term\":\"borrow checker\"},\
+                    {\"locations\":[{\"chapter\":\"Chapter One\",\"href\":\"dir/ch1.html#a002\"}],\
+                    \"term\":\"move\"}];\n\n";
+        assert!(got.starts_with(want), "Unexpected script: {}", got);
+    }
+
+    #[test]
+    fn test_write_if_changed() {
+        let dir = std::env::temp_dir().join(format!("mdbook-indexing-{}", std::process::id()));
+        let path = dir.join("theme/index-terms.js");
+        assert!(write_if_changed(&path, "abc").unwrap());
+        assert!(!write_if_changed(&path, "abc").unwrap());
+        assert!(write_if_changed(&path, "abcd").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abcd");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
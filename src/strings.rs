@@ -0,0 +1,306 @@
+//! Localized text that appears in the generated index.
+
+/// Built-in translations, keyed by language code.
+///
+/// The `see` and `see_also` templates have `{}` replaced by the entry that is referred to, the `description` template
+/// has `{}` replaced by the description of an entry, and the `occurrence` template has `{n}`, `{total}` and `{entry}`
+/// replaced by the position of an occurrence of an entry.
+const TRANSLATIONS: &[Translation] = &[
+    Translation {
+        language: "en",
+        title: "Index",
+        see: "see {}",
+        see_also: "see also {}",
+        symbols: "Symbols",
+        numbers: "Numbers",
+        glossary: "Glossary",
+        occurrence: "occurrence {n} of {total} of '{entry}'",
+        previous: "previous",
+        next: "next",
+        description: "— {}",
+        range: "–",
+        filter: "Filter entries",
+    },
+    Translation {
+        language: "de",
+        title: "Stichwortverzeichnis",
+        see: "siehe {}",
+        see_also: "siehe auch {}",
+        symbols: "Symbole",
+        numbers: "Zahlen",
+        glossary: "Glossar",
+        occurrence: "Vorkommen {n} von {total} von „{entry}“",
+        previous: "vorheriges",
+        next: "nächstes",
+        description: "— {}",
+        range: "–",
+        filter: "Einträge filtern",
+    },
+    Translation {
+        language: "es",
+        title: "Índice alfabético",
+        see: "véase {}",
+        see_also: "véase también {}",
+        symbols: "Símbolos",
+        numbers: "Números",
+        glossary: "Glosario",
+        occurrence: "aparición {n} de {total} de «{entry}»",
+        previous: "anterior",
+        next: "siguiente",
+        description: "— {}",
+        range: "–",
+        filter: "Filtrar entradas",
+    },
+    Translation {
+        language: "fr",
+        title: "Index",
+        see: "voir {}",
+        see_also: "voir aussi {}",
+        symbols: "Symboles",
+        numbers: "Nombres",
+        glossary: "Glossaire",
+        occurrence: "occurrence {n} sur {total} de « {entry} »",
+        previous: "précédente",
+        next: "suivante",
+        description: "— {}",
+        range: "–",
+        filter: "Filtrer les entrées",
+    },
+    Translation {
+        language: "it",
+        title: "Indice analitico",
+        see: "vedi {}",
+        see_also: "vedi anche {}",
+        symbols: "Simboli",
+        numbers: "Numeri",
+        glossary: "Glossario",
+        occurrence: "occorrenza {n} di {total} di «{entry}»",
+        previous: "precedente",
+        next: "successiva",
+        description: "— {}",
+        range: "–",
+        filter: "Filtra le voci",
+    },
+    Translation {
+        language: "ja",
+        title: "索引",
+        see: "{}を見よ",
+        see_also: "{}も見よ",
+        symbols: "記号",
+        numbers: "数字",
+        glossary: "用語集",
+        occurrence: "「{entry}」の出現箇所 {n}/{total}",
+        previous: "前へ",
+        next: "次へ",
+        description: "— {}",
+        range: "〜",
+        filter: "項目を絞り込む",
+    },
+    Translation {
+        language: "zh",
+        title: "索引",
+        see: "见{}",
+        see_also: "另见{}",
+        symbols: "符号",
+        numbers: "数字",
+        glossary: "术语表",
+        occurrence: "“{entry}”的出现位置 {n}/{total}",
+        previous: "上一个",
+        next: "下一个",
+        description: "— {}",
+        range: "–",
+        filter: "筛选条目",
+    },
+];
+
+/// Built-in text for a language, with fields as for [`Strings`].
+struct Translation {
+    language: &'static str,
+    title: &'static str,
+    see: &'static str,
+    see_also: &'static str,
+    symbols: &'static str,
+    numbers: &'static str,
+    glossary: &'static str,
+    occurrence: &'static str,
+    previous: &'static str,
+    next: &'static str,
+    description: &'static str,
+    range: &'static str,
+    filter: &'static str,
+}
+
+/// Text that appears in the generated index.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Strings {
+    /// Title of the index.
+    pub title: String,
+    /// Template for a see-instead reference.
+    pub see: String,
+    /// Template for a see-also reference.
+    pub see_also: String,
+    /// Label for the group of entries that start with a symbol.
+    pub symbols: String,
+    /// Label for the group of entries that start with a digit.
+    pub numbers: String,
+    /// Title of the glossary.
+    pub glossary: String,
+    /// Template for the position of an occurrence of an entry.
+    pub occurrence: String,
+    /// Link text for the previous occurrence of an entry.
+    pub previous: String,
+    /// Link text for the next occurrence of an entry.
+    pub next: String,
+    /// Template for the description of an entry, following its locators.
+    pub description: String,
+    /// Separator between the first and last locators of a range.
+    pub range: String,
+    /// Placeholder text for the box that filters the entries of the index.
+    pub filter: String,
+}
+
+impl Default for Strings {
+    fn default() -> Self {
+        Self::for_language(None)
+    }
+}
+
+impl Strings {
+    /// Return the built-in text for a language (e.g. "de" or "ja-JP"), falling back to English.
+    pub fn for_language(language: Option<&str>) -> Self {
+        // Only the primary language subtag is relevant.
+        let code = language
+            .unwrap_or_default()
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_lowercase();
+        let translation = TRANSLATIONS
+            .iter()
+            .find(|translation| translation.language == code)
+            .unwrap_or_else(|| {
+                if language.is_some() {
+                    log::info!("No built-in index text for language '{code}', using English");
+                }
+                &TRANSLATIONS[0]
+            });
+        Self {
+            title: translation.title.to_string(),
+            see: translation.see.to_string(),
+            see_also: translation.see_also.to_string(),
+            symbols: translation.symbols.to_string(),
+            numbers: translation.numbers.to_string(),
+            glossary: translation.glossary.to_string(),
+            occurrence: translation.occurrence.to_string(),
+            previous: translation.previous.to_string(),
+            next: translation.next.to_string(),
+            description: translation.description.to_string(),
+            range: translation.range.to_string(),
+            filter: translation.filter.to_string(),
+        }
+    }
+
+    /// Override one of the strings, returning `false` if `key` is not recognized.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        let field = match key {
+            "title" => &mut self.title,
+            "see" => &mut self.see,
+            "see_also" => &mut self.see_also,
+            "symbols" => &mut self.symbols,
+            "numbers" => &mut self.numbers,
+            "glossary" => &mut self.glossary,
+            "occurrence" => &mut self.occurrence,
+            "previous" => &mut self.previous,
+            "next" => &mut self.next,
+            "description" => &mut self.description,
+            "range" => &mut self.range,
+            "filter" => &mut self.filter,
+            _ => return false,
+        };
+        *field = value.to_string();
+        true
+    }
+
+    /// Generate a see-instead reference to `target`.
+    pub fn see(&self, target: &str) -> String {
+        fill(&self.see, target)
+    }
+
+    /// Generate a see-also reference to `targets`.
+    pub fn see_also(&self, targets: &str) -> String {
+        fill(&self.see_also, targets)
+    }
+
+    /// Generate the text for the description of an entry.
+    pub fn description(&self, description: &str) -> String {
+        fill(&self.description, description)
+    }
+
+    /// Describe the position of the `n`-th (from 1) of `total` occurrences of `entry`.
+    pub fn occurrence(&self, n: usize, total: usize, entry: &str) -> String {
+        self.occurrence
+            .replace("{n}", &n.to_string())
+            .replace("{total}", &total.to_string())
+            .replace("{entry}", entry)
+    }
+}
+
+/// Fill in the `{}` placeholder in a template, appending the value if there is no placeholder.
+fn fill(template: &str, value: &str) -> String {
+    if template.contains("{}") {
+        template.replace("{}", value)
+    } else {
+        format!("{template} {value}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_language() {
+        let cases = [
+            (None, "Index", "see X"),
+            (Some("en"), "Index", "see X"),
+            (Some("de-DE"), "Stichwortverzeichnis", "siehe X"),
+            (Some("ja_JP"), "索引", "Xを見よ"),
+            (Some("xx"), "Index", "see X"),
+        ];
+        for (language, want_title, want_see) in cases {
+            let got = Strings::for_language(language);
+            assert_eq!(
+                got.title, want_title,
+                "Mismatch for language: {:?}",
+                language
+            );
+            assert_eq!(
+                got.see("X"),
+                want_see,
+                "Mismatch for language: {:?}",
+                language
+            );
+        }
+    }
+
+    #[test]
+    fn test_set() {
+        let mut strings = Strings::default();
+        assert!(strings.set("see_also", "cf."));
+        assert_eq!(strings.see_also("X"), "cf. X");
+        assert!(!strings.set("unknown", "value"));
+    }
+
+    #[test]
+    fn test_occurrence() {
+        let cases = [
+            (None, "occurrence 2 of 7 of 'X'"),
+            (Some("de"), "Vorkommen 2 von 7 von „X“"),
+            (Some("ja"), "「X」の出現箇所 2/7"),
+        ];
+        for (language, want) in cases {
+            let got = Strings::for_language(language).occurrence(2, 7, "X");
+            assert_eq!(got, want, "Mismatch for language: {:?}", language);
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Tests of the library API, driving the preprocessor in the same way as mdbook.
+
+use mdbook_indexing::{backend, canonicalize, split_reading, Index};
+use mdbook_preprocessor::{
+    book::{Book, BookItem, Chapter},
+    config::Config,
+    Preprocessor, PreprocessorContext,
+};
+use std::path::{Path, PathBuf};
+
+/// Build a preprocessor context for `renderer`, with the given `book.toml` contents.
+fn context(book_toml: &str, renderer: &str) -> PreprocessorContext {
+    let config: Config = book_toml.parse().unwrap();
+    // A root with no index.toml file in it.
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+    PreprocessorContext::new(root, config, renderer.to_string())
+}
+
+/// Build a book from (name, path, content) triples.
+fn book(chapters: &[(&str, &str, &str)]) -> Book {
+    let mut book = Book::new();
+    for (name, path, content) in chapters {
+        book.push_item(Chapter::new(name, content.to_string(), path, Vec::new()));
+    }
+    book
+}
+
+/// Return the content of each chapter of `book`.
+fn contents(book: &Book) -> Vec<String> {
+    book.iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(chap) => Some(chap.content.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn test_run() {
+    let chapters = [
+        ("One", "ch1.md", "Some {{i:borrow}} and {{hi:move}}."),
+        ("Two", "dir/ch2.md", "More {{ii:borrow}}."),
+        ("Index", "index.md", ""),
+    ];
+    let cases = [
+        (
+            "html",
+            vec![
+                "Some <a name=\"a001\"></a>borrow and <a name=\"a002\"></a>.",
+                "More <a name=\"a001\"></a>*borrow*.",
+                "# Index\n\nborrow, [1](ch1.md#a001), [2](dir/ch2.md#a001)<br/>\nmove, [1](ch1.md#a002)<br/>\n",
+            ],
+        ),
+        (
+            "markdown",
+            vec!["Some borrow and .", "More *borrow*.", ""],
+        ),
+    ];
+    for (renderer, want) in cases {
+        let ctx = context("[preprocessor.indexing]\n", renderer);
+        let index = Index::new(&ctx).unwrap();
+        let got = contents(&index.run(&ctx, book(&chapters)).unwrap());
+        assert_eq!(got, want, "Mismatch for renderer: {}", renderer);
+    }
+}
+
+#[test]
+fn test_config_errors() {
+    let cases = [
+        "[preprocessor.indexing]\nuse_chapter_names = \"true\"\n",
+        "[preprocessor.indexing]\nbacklinks = \"sometimes\"\n",
+        "[preprocessor.indexing.backend]\nhtml = \"htlm\"\n",
+        "[preprocessor.indexing.nest_under]\nx = \"y\"\ny = \"z\"\nz = \"x\"\n",
+    ];
+    for input in cases {
+        let ctx = context(input, "html");
+        assert!(
+            Index::new(&ctx).is_err(),
+            "Unexpected success for input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn test_collect_and_render() {
+    let ctx = context(
+        "[preprocessor.indexing.see_instead]\n\"borrowck\" = \"borrow checker\"\n",
+        "html",
+    );
+    let index = Index::new(&ctx).unwrap();
+    let html = index.backend("html");
+    let path = Some(PathBuf::from("ch1.md"));
+    let got = index.process_chapter(
+        html.as_ref(),
+        &path,
+        None,
+        "One",
+        "The {{i:borrow  checker}} (or {{i:borrowck}}).",
+    );
+    assert_eq!(
+        got,
+        "The <a name=\"a001\"></a>borrow  checker (or <a name=\"a002\"></a>borrowck)."
+    );
+
+    let entries = index.entries();
+    let names: Vec<&str> = entries.iter().map(|(entry, _)| entry.as_str()).collect();
+    assert_eq!(names, vec!["borrow checker"]);
+    let anchors: Vec<&str> = entries[0].1.iter().map(|loc| loc.anchor.as_str()).collect();
+    assert_eq!(anchors, vec!["a001", "a002"]);
+    assert_eq!(entries[0].1[0].name, "One");
+
+    let latex = backend::by_name("latex").unwrap();
+    assert_eq!(
+        latex.marker(
+            &index,
+            &backend::Marker {
+                path: None,
+                content: "borrowck",
+                entry: "borrowck",
+                target: "borrow checker",
+                visible: true,
+                italic: false,
+                anchor: None,
+            }
+        ),
+        "`\\index{borrow checker}\\index{borrowck|see{borrow checker}}`{=latex}borrowck"
+    );
+}
+
+#[test]
+fn test_helpers() {
+    assert_eq!(
+        canonicalize("[borrow\n  checker](ch1.md)"),
+        "borrow checker"
+    );
+    assert_eq!(
+        split_reading("所有権@しょゆうけん"),
+        ("所有権", Some("しょゆうけん"))
+    );
+    assert_eq!(backend::text_to_asciidoc("`Vec<T>`"), "Vec&lt;T&gt;");
+    let mut text = "move, semantics".to_string();
+    backend::asciidoc_protect(&mut text);
+    assert_eq!(text, "\"move, semantics\"");
+}
+
+#[test]
+fn test_glossary_chapter() {
+    let glossary = (
+        "Glossary",
+        "glossary.md",
+        "**Borrow**: see {{i:borrowing}}.",
+    );
+    let index = ("Index", "index.md", "");
+    let cases = [
+        // A hand-written glossary is indexed like any other chapter.
+        (
+            vec![("One", "ch1.md", "Some {{i:borrowing}}."), glossary, index],
+            "**Borrow**: see <a name=\"a001\"></a>borrowing.",
+            "# Index\n\nborrowing, [1](ch1.md#a001), [2](glossary.md#a001)<br/>\n",
+        ),
+        // Glossary markup means the glossary chapter is generated.
+        (
+            vec![("One", "ch1.md", "Some {{g:borrowing}}."), glossary, index],
+            "# Glossary\n\n<a name=\"glossary-borrowing\"></a>**borrowing**: ([Index](index.md#index-borrowing))\n\n",
+            "# Index\n\n<a name=\"index-borrowing\"></a>borrowing, [1](ch1.md#a001), \
+             [Glossary](glossary.md#glossary-borrowing)<br/>\n",
+        ),
+    ];
+    for (chapters, want_glossary, want_index) in cases {
+        let ctx = context("[preprocessor.indexing]\n", "html");
+        let index = Index::new(&ctx).unwrap();
+        let got = contents(&index.run(&ctx, book(&chapters)).unwrap());
+        assert_eq!(
+            got[1], want_glossary,
+            "Mismatch for chapters: {:?}",
+            chapters
+        );
+        assert_eq!(got[2], want_index, "Mismatch for chapters: {:?}", chapters);
+    }
+}
+
+#[test]
+fn test_search_terms_file() {
+    let root = std::env::temp_dir().join(format!("mdbook-indexing-search-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    let config: Config = "[preprocessor.indexing]\nsearch_terms = \"index-terms.js\"\n"
+        .parse()
+        .unwrap();
+    let ctx = PreprocessorContext::new(root.clone(), config, "html".to_string());
+    let index = Index::new(&ctx).unwrap();
+    index
+        .run(&ctx, book(&[("One", "ch1.md", "{{i:borrow}}")]))
+        .unwrap();
+
+    // The file is where `additional-js = ["index-terms.js"]` finds it, and not in the source directory.
+    let script = std::fs::read_to_string(root.join("index-terms.js")).unwrap();
+    assert!(
+        script.contains("\"term\":\"borrow\""),
+        "Unexpected script: {}",
+        script
+    );
+    assert!(!root.join("src").exists());
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_front_matter() {
+    let cases = [
+        ("+++\nindex = [\"unsafe\"]\n+++\ntext", "\n\ntext"),
+        // A block that parses as TOML is removed, even if it isn't valid front matter.
+        ("+++\nindexes = [\"unsafe\"]\n+++\ntext", "text"),
+        ("+++\nindex = \"unsafe\"\n+++\ntext", "text"),
+    ];
+    for (input, want) in cases {
+        let ctx = context("[preprocessor.indexing]\n", "markdown");
+        let index = Index::new(&ctx).unwrap();
+        let got = contents(&index.run(&ctx, book(&[("One", "ch1.md", input)])).unwrap());
+        assert_eq!(got, vec![want], "Mismatch for input: {}", input);
+    }
+}
+
+#[test]
+fn test_unmarked_parent() {
+    // The parent of a nested entry need not be marked anywhere itself.
+    let chapters = [("One", "ch1.md", "{{i:b}}"), ("Index", "index.md", "")];
+    let cases = [
+        (
+            "html",
+            "# Index\n\na<br/>\n&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;&nbsp;b, [1](ch1.md#a001)<br/>\n",
+        ),
+        (
+            "epub",
+            "<section epub:type=\"index\">\n<h1>Index</h1>\n<ul epub:type=\"index-entry-list\">\n\
+             <li epub:type=\"index-entry\"><span epub:type=\"index-term\">a</span>\n\
+             <ul epub:type=\"index-entry-list\">\n\
+             <li epub:type=\"index-entry\"><span epub:type=\"index-term\">b</span>, \
+             <a epub:type=\"index-locator\" href=\"ch1.html#a001\">1</a></li>\n</ul>\n</li>\n</ul>\n</section>\n",
+        ),
+    ];
+    for (renderer, want) in cases {
+        let ctx = context(
+            "[preprocessor.indexing.nest_under]\n\"b\" = \"a\"\n",
+            renderer,
+        );
+        let index = Index::new(&ctx).unwrap();
+        let got = contents(&index.run(&ctx, book(&chapters)).unwrap());
+        assert_eq!(got[1], want, "Mismatch for renderer: {}", renderer);
+    }
+}